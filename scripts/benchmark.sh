@@ -80,6 +80,8 @@ load_data() {
         --output "$data_dir/trades_$size.csv" \
         --instruments-output "$data_dir/instruments.csv" \
         --constituents-output "$data_dir/constituents.csv" \
+        --fx-rates-output "$data_dir/fx_rates.csv" \
+        --price-history-output "$data_dir/price_history.csv" \
         --explode-constituents \
         --seed 42 2>/dev/null
 
@@ -97,6 +99,14 @@ load_data() {
     docker exec -i pivot-clickhouse clickhouse-client --query \
         "INSERT INTO pivot.constituents FORMAT CSVWithNames" < "$data_dir/constituents.csv" 2>/dev/null
 
+    # Load FX rates
+    docker exec -i pivot-clickhouse clickhouse-client --query \
+        "INSERT INTO pivot.fx_rates FORMAT CSVWithNames" < "$data_dir/fx_rates.csv" 2>/dev/null
+
+    # Load price history
+    docker exec -i pivot-clickhouse clickhouse-client --query \
+        "INSERT INTO pivot.price_history FORMAT CSVWithNames" < "$data_dir/price_history.csv" 2>/dev/null
+
     # Load trades
     docker exec -i pivot-clickhouse clickhouse-client --query \
         "INSERT INTO pivot.trades_1d FORMAT CSVWithNames" < "$data_dir/trades_$size.csv" 2>/dev/null
@@ -6,6 +6,7 @@ pub struct Config {
     pub clickhouse: ClickHouseConfig,
     pub redis: RedisConfig,
     pub cache: CacheConfig,
+    pub concentration: ConcentrationConfig,
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +32,13 @@ pub struct CacheConfig {
     pub ttl_seconds: u64,
 }
 
+/// Default flag levels for `/api/v1/concentration`, as a percent of gross exposure.
+#[derive(Debug, Clone)]
+pub struct ConcentrationConfig {
+    pub warn_pct: f64,
+    pub breach_pct: f64,
+}
+
 impl Config {
     pub fn from_env() -> Result<Self, env::VarError> {
         Ok(Config {
@@ -61,6 +69,16 @@ impl Config {
                     .parse()
                     .unwrap_or(300),
             },
+            concentration: ConcentrationConfig {
+                warn_pct: env::var("CONCENTRATION_WARN_PCT")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()
+                    .unwrap_or(5.0),
+                breach_pct: env::var("CONCENTRATION_BREACH_PCT")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()
+                    .unwrap_or(10.0),
+            },
         })
     }
 }
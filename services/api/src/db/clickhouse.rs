@@ -1,5 +1,9 @@
 use clickhouse::Client;
+use std::collections::HashMap;
+
 use crate::config::ClickHouseConfig;
+use crate::error::ApiError;
+use crate::query::BoundQuery;
 
 pub fn create_client(config: &ClickHouseConfig) -> Client {
     Client::default()
@@ -11,3 +15,35 @@ pub async fn health_check(client: &Client) -> Result<(), clickhouse::error::Erro
     client.query("SELECT 1").execute().await?;
     Ok(())
 }
+
+/// Runs `query` over the HTTP interface and returns each `JSONEachRow` line as a map.
+pub async fn fetch_json_rows(
+    url: &str,
+    query: &BoundQuery,
+) -> Result<Vec<HashMap<String, serde_json::Value>>, ApiError> {
+    let http_url = format!("{}/?default_format=JSONEachRow", url.trim_end_matches('/'));
+    let response = reqwest::Client::new()
+        .post(&http_url)
+        .query(&query.params.to_http_pairs())
+        .body(query.sql.clone())
+        .send()
+        .await
+        .map_err(|e| ApiError::Database(e.to_string()))?;
+
+    if !response.status().is_success() {
+        let body = response.text().await.unwrap_or_default();
+        tracing::error!("ClickHouse error: {}", body);
+        return Err(ApiError::Database(body));
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| ApiError::Database(e.to_string()))?;
+
+    Ok(body
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
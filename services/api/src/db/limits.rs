@@ -0,0 +1,124 @@
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::ApiError;
+use crate::models::request::{LimitKind, RiskLimitSpec};
+use crate::models::response::RiskLimit;
+use crate::query::params::{BoundQuery, QueryParams};
+
+/// Latest live version of every limit; tombstones and superseded versions drop out.
+const SELECT_LIMITS: &str = "SELECT limit_id, name, kind, dimension, scope_value, metric, absolute, max_value \
+                             FROM pivot.risk_limits FINAL WHERE is_deleted = 0";
+
+#[derive(Debug, Row, Deserialize)]
+struct RiskLimitRow {
+    limit_id: String,
+    name: String,
+    kind: String,
+    dimension: String,
+    scope_value: String,
+    metric: String,
+    absolute: u8,
+    max_value: f64,
+}
+
+impl RiskLimitRow {
+    fn into_limit(self) -> Result<RiskLimit, ApiError> {
+        let mut spec = json!({
+            "name": self.name,
+            "kind": self.kind,
+            "dimension": self.dimension,
+            "max_value": self.max_value,
+        });
+        if !self.scope_value.is_empty() {
+            spec["scope_value"] = Value::String(self.scope_value);
+        }
+        if !self.metric.is_empty() {
+            spec["metric"] = Value::String(self.metric);
+            spec["absolute"] = Value::Bool(self.absolute != 0);
+        }
+
+        let spec = serde_json::from_value(spec).map_err(|e| {
+            ApiError::Internal(format!("Stored limit '{}' is invalid: {}", self.limit_id, e))
+        })?;
+        Ok(RiskLimit { limit_id: self.limit_id, spec })
+    }
+}
+
+pub async fn list_limits(client: &Client) -> Result<Vec<RiskLimit>, ApiError> {
+    let rows: Vec<RiskLimitRow> = client
+        .query(&format!("{} ORDER BY name, limit_id", SELECT_LIMITS))
+        .fetch_all()
+        .await?;
+    rows.into_iter().map(RiskLimitRow::into_limit).collect()
+}
+
+pub async fn get_limit(client: &Client, limit_id: &str) -> Result<Option<RiskLimit>, ApiError> {
+    let mut params = QueryParams::new();
+    let sql = format!("{} AND limit_id = {}", SELECT_LIMITS, params.bind("String", limit_id));
+    let rows: Vec<RiskLimitRow> = BoundQuery::new(sql, params)
+        .to_query(client)
+        .fetch_all()
+        .await?;
+    rows.into_iter().next().map(RiskLimitRow::into_limit).transpose()
+}
+
+/// Fresh identifier for a new limit.
+pub async fn new_limit_id(client: &Client) -> Result<String, ApiError> {
+    Ok(client
+        .query("SELECT toString(generateUUIDv4())")
+        .fetch_one::<String>()
+        .await?)
+}
+
+/// Writes a new version of `limit`, replacing any earlier one with the same id.
+pub async fn write_limit(client: &Client, limit: &RiskLimit) -> Result<(), ApiError> {
+    insert_version(client, &limit.limit_id, Some(&limit.spec)).await
+}
+
+/// Writes a tombstone for `limit_id`.
+pub async fn delete_limit(client: &Client, limit_id: &str) -> Result<(), ApiError> {
+    insert_version(client, limit_id, None).await
+}
+
+async fn insert_version(
+    client: &Client,
+    limit_id: &str,
+    spec: Option<&RiskLimitSpec>,
+) -> Result<(), ApiError> {
+    let mut params = QueryParams::new();
+    let (kind, metric, absolute) = match spec.map(|s| &s.kind) {
+        Some(LimitKind::Metric { metric, absolute }) => ("metric", enum_name(metric)?, *absolute),
+        Some(LimitKind::Concentration) => ("concentration", String::new(), false),
+        None => ("", String::new(), false),
+    };
+    let values = [
+        params.bind("String", limit_id),
+        params.bind("String", spec.map_or("", |s| s.name.as_str())),
+        params.bind("String", kind),
+        params.bind("String", spec.map(|s| s.dimension.to_column()).unwrap_or("")),
+        params.bind("String", spec.and_then(|s| s.scope_value.as_deref()).unwrap_or("")),
+        params.bind("String", metric),
+        params.bind("UInt8", absolute as u8),
+        params.bind("Float64", spec.map_or(0.0, |s| s.max_value)),
+        params.bind("UInt8", spec.is_none() as u8),
+    ];
+
+    let sql = format!(
+        "INSERT INTO pivot.risk_limits \
+         (limit_id, name, kind, dimension, scope_value, metric, absolute, max_value, is_deleted) \
+         SELECT {}",
+        values.join(", ")
+    );
+    BoundQuery::new(sql, params).to_query(client).execute().await?;
+    Ok(())
+}
+
+/// Serialized name of a unit enum variant, e.g. `gross_notional`.
+fn enum_name<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    match serde_json::to_value(value)? {
+        Value::String(name) => Ok(name),
+        other => Err(ApiError::Internal(format!("Unexpected enum value {}", other))),
+    }
+}
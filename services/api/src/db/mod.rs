@@ -1,4 +1,6 @@
 pub mod clickhouse;
+pub mod limits;
 pub mod models;
+pub mod shock_sets;
 
 pub use self::clickhouse::create_client;
@@ -20,7 +20,10 @@ pub struct ConstituentRow {
     pub constituent_symbol: String,
     pub weight: f64,
     pub shares_per_unit: f64,
+    #[serde(rename = "effective_date_str")]
     pub effective_date: String,
+    #[serde(rename = "expiry_date_str")]
+    pub expiry_date: String,
 }
 
 fn deserialize_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
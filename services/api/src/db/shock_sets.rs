@@ -0,0 +1,64 @@
+use clickhouse::{Client, Row};
+use serde::Deserialize;
+
+use crate::error::ApiError;
+use crate::models::response::ShockSet;
+use crate::query::params::{BoundQuery, QueryParams};
+
+/// Latest version of every shock set.
+const SELECT_SHOCK_SETS: &str = "SELECT name, description, shocks FROM pivot.shock_sets FINAL";
+
+#[derive(Debug, Row, Deserialize)]
+struct ShockSetRow {
+    name: String,
+    description: String,
+    shocks: String,
+}
+
+impl ShockSetRow {
+    fn into_shock_set(self) -> Result<ShockSet, ApiError> {
+        let shocks = serde_json::from_str(&self.shocks).map_err(|e| {
+            ApiError::Internal(format!("Stored shock set '{}' is invalid: {}", self.name, e))
+        })?;
+        Ok(ShockSet {
+            name: self.name,
+            description: self.description,
+            shocks,
+        })
+    }
+}
+
+pub async fn list_shock_sets(client: &Client) -> Result<Vec<ShockSet>, ApiError> {
+    let rows: Vec<ShockSetRow> = client
+        .query(&format!("{} ORDER BY name", SELECT_SHOCK_SETS))
+        .fetch_all()
+        .await?;
+    rows.into_iter().map(ShockSetRow::into_shock_set).collect()
+}
+
+pub async fn get_shock_set(client: &Client, name: &str) -> Result<Option<ShockSet>, ApiError> {
+    let mut params = QueryParams::new();
+    let sql = format!("{} WHERE name = {}", SELECT_SHOCK_SETS, params.bind("String", name));
+    let rows: Vec<ShockSetRow> = BoundQuery::new(sql, params)
+        .to_query(client)
+        .fetch_all()
+        .await?;
+    rows.into_iter().next().map(ShockSetRow::into_shock_set).transpose()
+}
+
+/// Writes a new version of `set`, replacing any earlier one with the same name.
+pub async fn write_shock_set(client: &Client, set: &ShockSet) -> Result<(), ApiError> {
+    let mut params = QueryParams::new();
+    let values = [
+        params.bind("String", &set.name),
+        params.bind("String", &set.description),
+        params.bind("String", serde_json::to_string(&set.shocks)?),
+    ];
+
+    let sql = format!(
+        "INSERT INTO pivot.shock_sets (name, description, shocks) SELECT {}",
+        values.join(", ")
+    );
+    BoundQuery::new(sql, params).to_query(client).execute().await?;
+    Ok(())
+}
@@ -5,6 +5,7 @@ use std::fmt;
 #[derive(Debug)]
 pub enum ApiError {
     BadRequest(String),
+    NotFound(String),
     Database(String),
     Cache(String),
     QueryValidation(String),
@@ -15,6 +16,7 @@ impl fmt::Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ApiError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            ApiError::NotFound(msg) => write!(f, "Not found: {}", msg),
             ApiError::Database(msg) => write!(f, "Database error: {}", msg),
             ApiError::Cache(msg) => write!(f, "Cache error: {}", msg),
             ApiError::QueryValidation(msg) => write!(f, "Query validation error: {}", msg),
@@ -34,6 +36,7 @@ impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
             ApiError::QueryValidation(_) => StatusCode::BAD_REQUEST,
             ApiError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Cache(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -44,6 +47,7 @@ impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
         let message = match self {
             ApiError::BadRequest(msg) => msg.clone(),
+            ApiError::NotFound(msg) => msg.clone(),
             ApiError::QueryValidation(msg) => msg.clone(),
             ApiError::Database(_) => "Database error".to_string(),
             ApiError::Cache(_) => "Cache error".to_string(),
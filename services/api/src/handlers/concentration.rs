@@ -0,0 +1,95 @@
+use actix_web::{web, HttpResponse};
+use clickhouse::Row;
+use serde::Deserialize;
+use std::time::Instant;
+
+use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
+use crate::error::ApiError;
+use crate::models::request::ConcentrationQuery;
+use crate::models::response::{ConcentrationResponse, ConcentrationRow, QueryMetadata};
+use crate::query::concentration::ConcentrationQueryBuilder;
+use crate::AppState;
+
+#[derive(Debug, Row, Deserialize)]
+struct ConcentrationDbRow {
+    underlying_symbol: String,
+    direct_exposure: f64,
+    etf_exposure: f64,
+    residual_exposure: f64,
+    total_exposure: f64,
+    gross_exposure: f64,
+}
+
+pub async fn handler(
+    state: web::Data<AppState>,
+    query: web::Query<ConcentrationQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+
+    let builder = ConcentrationQueryBuilder::from_query(&query, &state.config.concentration)?;
+
+    // Check cache first
+    let cache_key = generate_cache_key("concentration", &serde_json::to_string(&query.0)?);
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<ConcentrationResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let db_query = builder.build();
+    tracing::debug!("Executing concentration query: {} {:?}", db_query.sql, db_query.params);
+
+    let rows: Vec<ConcentrationDbRow> = db_query
+        .to_query(&state.clickhouse)
+        .fetch_all()
+        .await?;
+
+    let gross_exposure = rows.first().map(|r| r.gross_exposure).unwrap_or(0.0);
+    let data: Vec<ConcentrationRow> = rows
+        .into_iter()
+        .map(|r| {
+            let pct_of_gross = if gross_exposure > 0.0 {
+                r.total_exposure.abs() / gross_exposure * 100.0
+            } else {
+                0.0
+            };
+            ConcentrationRow {
+                underlying_symbol: r.underlying_symbol,
+                direct_exposure: r.direct_exposure,
+                etf_exposure: r.etf_exposure,
+                residual_exposure: r.residual_exposure,
+                total_exposure: r.total_exposure,
+                pct_of_gross,
+                status: builder.status(pct_of_gross),
+            }
+        })
+        .collect();
+
+    let response = ConcentrationResponse {
+        metadata: QueryMetadata {
+            total_rows: data.len() as u64,
+            returned_rows: data.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some(builder.source().name().to_string()),
+            next_cursor: None,
+        },
+        data,
+        gross_exposure,
+        warn_pct: builder.warn_pct(),
+        breach_pct: builder.breach_pct(),
+    };
+
+    // Cache the response
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
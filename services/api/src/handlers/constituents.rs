@@ -3,9 +3,11 @@ use std::time::Instant;
 
 use crate::db::models::ConstituentRow;
 use crate::error::ApiError;
-use crate::models::request::ConstituentsQuery;
+use crate::models::request::{ConstituentDiffQuery, ConstituentsQuery};
 use crate::models::response::{Constituent, ConstituentsResponse};
 use crate::cache::redis::{get_cached, set_cached};
+use crate::query::constituents::{basket_query, diff_baskets};
+use crate::query::params::validate_date;
 use crate::AppState;
 
 const CACHE_KEY_ALL: &str = "constituents:all";
@@ -17,8 +19,14 @@ pub async fn handler(
 ) -> Result<HttpResponse, ApiError> {
     let start = Instant::now();
 
+    if let Some(ref as_of) = query.as_of {
+        validate_date("as_of", as_of)?;
+    }
+
     // Check cache first (only for unfiltered requests)
-    let use_cache = query.parent_symbol.is_none() && query.constituent_symbol.is_none();
+    let use_cache = query.parent_symbol.is_none()
+        && query.constituent_symbol.is_none()
+        && query.as_of.is_none();
 
     if use_cache && state.config.cache.enabled {
         let mut redis = state.redis.clone();
@@ -28,33 +36,65 @@ pub async fn handler(
         }
     }
 
-    // Build query
-    let mut sql = "SELECT parent_symbol, constituent_symbol, weight, shares_per_unit, toString(effective_date) as effective_date FROM pivot.constituents".to_string();
-    let mut conditions = Vec::new();
+    let constituents = fetch_basket(
+        &state,
+        query.parent_symbol.as_deref(),
+        query.constituent_symbol.as_deref(),
+        query.as_of.as_deref(),
+    )
+    .await?;
 
-    if let Some(ref ps) = query.parent_symbol {
-        conditions.push(format!("parent_symbol = '{}'", escape_string(ps)));
-    }
-    if let Some(ref cs) = query.constituent_symbol {
-        conditions.push(format!("constituent_symbol = '{}'", escape_string(cs)));
-    }
+    let response = ConstituentsResponse {
+        count: constituents.len(),
+        constituents,
+    };
 
-    if !conditions.is_empty() {
-        sql.push_str(" WHERE ");
-        sql.push_str(&conditions.join(" AND "));
+    // Cache unfiltered response
+    if use_cache && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, CACHE_KEY_ALL, &response, CACHE_TTL).await;
     }
 
-    sql.push_str(" ORDER BY parent_symbol, weight DESC");
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Names added, removed and reweighted in a basket between two dates.
+pub async fn diff_handler(
+    state: web::Data<AppState>,
+    query: web::Query<ConstituentDiffQuery>,
+) -> Result<HttpResponse, ApiError> {
+    validate_date("from_date", &query.from_date)?;
+    validate_date("to_date", &query.to_date)?;
+
+    let parent = Some(query.parent_symbol.as_str());
+    let from = fetch_basket(&state, parent, None, Some(&query.from_date)).await?;
+    let to = fetch_basket(&state, parent, None, Some(&query.to_date)).await?;
+
+    Ok(HttpResponse::Ok().json(diff_baskets(
+        &query.parent_symbol,
+        &query.from_date,
+        &query.to_date,
+        from,
+        to,
+    )))
+}
+
+async fn fetch_basket(
+    state: &AppState,
+    parent_symbol: Option<&str>,
+    constituent_symbol: Option<&str>,
+    as_of: Option<&str>,
+) -> Result<Vec<Constituent>, ApiError> {
+    let db_query = basket_query(parent_symbol, constituent_symbol, as_of);
 
-    tracing::debug!("Executing constituents query: {}", sql);
+    tracing::debug!("Executing constituents query: {} {:?}", db_query.sql, db_query.params);
 
-    let rows: Vec<ConstituentRow> = state
-        .clickhouse
-        .query(&sql)
+    let rows: Vec<ConstituentRow> = db_query
+        .to_query(&state.clickhouse)
         .fetch_all()
         .await?;
 
-    let constituents: Vec<Constituent> = rows
+    Ok(rows
         .into_iter()
         .map(|r| Constituent {
             parent_symbol: r.parent_symbol,
@@ -62,26 +102,7 @@ pub async fn handler(
             weight: r.weight,
             shares_per_unit: r.shares_per_unit,
             effective_date: r.effective_date,
+            expiry_date: r.expiry_date,
         })
-        .collect();
-
-    let response = ConstituentsResponse {
-        count: constituents.len(),
-        constituents,
-    };
-
-    // Cache unfiltered response
-    if use_cache && state.config.cache.enabled {
-        let mut redis = state.redis.clone();
-        let _ = set_cached(&mut redis, CACHE_KEY_ALL, &response, CACHE_TTL).await;
-    }
-
-    Ok(HttpResponse::Ok().json(response))
-}
-
-fn escape_string(s: &str) -> String {
-    s.replace('\'', "''")
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == ' ')
-        .collect()
+        .collect())
 }
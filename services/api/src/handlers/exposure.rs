@@ -6,6 +6,9 @@ use std::time::Instant;
 use crate::error::ApiError;
 use crate::models::request::{ExposureQuery, ExposureView};
 use crate::models::response::{ExposureResponse, ExposureRow, QueryMetadata};
+use crate::query::fx::{converted_source, validate_currency};
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+use crate::query::SourceTable;
 use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
 use crate::AppState;
 
@@ -45,6 +48,10 @@ pub async fn handler(
             group_by, ALLOWED_GROUP_BY
         )));
     }
+    validate_date("trade_date", &query.trade_date)?;
+    if let Some(ref currency) = query.reporting_currency {
+        validate_currency(currency)?;
+    }
 
     // Check cache first
     if !query.cache_bypass && state.config.cache.enabled {
@@ -63,31 +70,48 @@ pub async fn handler(
     let exposure_filter = match query.view {
         ExposureView::TopLevel => "exposure_type IN ('Direct', 'ETF', 'ETC')",
         ExposureView::LookThrough => "exposure_type IN ('Direct', 'Constituent')",
-        ExposureView::All => "1=1",
+        ExposureView::QueryLookThrough | ExposureView::All => "1=1",
+    };
+    let source = match query.view {
+        ExposureView::QueryLookThrough => SourceTable::LookThrough,
+        _ => SourceTable::Trades,
+    };
+    // Look-through expands each trade into one row per leg, so count distinct trades
+    let trade_count = match source {
+        SourceTable::LookThrough => "uniqExact(trade_id)",
+        _ => "count()",
     };
 
+    let mut params = QueryParams::new();
+    let from = match query.reporting_currency {
+        Some(ref currency) => converted_source(source.table(), currency, &mut params),
+        None => source.table().to_string(),
+    };
     let sql = format!(
         "SELECT
             toString({}) AS group_value,
             sum(notional) AS total_notional,
             sum(pnl) AS total_pnl,
-            count() AS trade_count
-         FROM pivot.trades_1d
-         WHERE trade_date = '{}' AND {}
+            {} AS trade_count
+         FROM {}
+         WHERE trade_date = {} AND {}
          GROUP BY {}
          ORDER BY total_notional DESC
          LIMIT 100",
         group_by,
-        escape_string(&query.trade_date),
+        trade_count,
+        from,
+        params.bind("Date", &query.trade_date),
         exposure_filter,
         group_by
     );
 
-    tracing::debug!("Executing exposure query: {}", sql);
+    let db_query = BoundQuery::new(sql, params);
 
-    let rows: Vec<ExposureDbRow> = state
-        .clickhouse
-        .query(&sql)
+    tracing::debug!("Executing exposure query: {} {:?}", db_query.sql, db_query.params);
+
+    let rows: Vec<ExposureDbRow> = db_query
+        .to_query(&state.clickhouse)
         .fetch_all()
         .await?;
 
@@ -107,6 +131,8 @@ pub async fn handler(
             returned_rows: data.len(),
             query_time_ms: start.elapsed().as_millis() as u64,
             cached: false,
+            source_table: Some(source.name().to_string()),
+            next_cursor: None,
         },
         data,
     };
@@ -120,10 +146,3 @@ pub async fn handler(
 
     Ok(HttpResponse::Ok().json(response))
 }
-
-fn escape_string(s: &str) -> String {
-    s.replace('\'', "''")
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
-        .collect()
-}
@@ -6,6 +6,7 @@ use crate::error::ApiError;
 use crate::models::request::InstrumentsQuery;
 use crate::models::response::{Instrument, InstrumentsResponse};
 use crate::cache::redis::{get_cached, set_cached};
+use crate::query::params::{BoundQuery, QueryParams};
 use crate::AppState;
 
 const CACHE_KEY: &str = "instruments:all";
@@ -31,12 +32,13 @@ pub async fn handler(
     // Build query
     let mut sql = "SELECT symbol, name, asset_class, instrument_type, currency, exchange, sector, is_composite FROM pivot.instruments".to_string();
     let mut conditions = Vec::new();
+    let mut params = QueryParams::new();
 
     if let Some(ref ac) = query.asset_class {
-        conditions.push(format!("asset_class = '{}'", escape_string(ac)));
+        conditions.push(format!("asset_class = {}", params.bind("String", ac)));
     }
     if let Some(ref it) = query.instrument_type {
-        conditions.push(format!("instrument_type = '{}'", escape_string(it)));
+        conditions.push(format!("instrument_type = {}", params.bind("String", it)));
     }
 
     if !conditions.is_empty() {
@@ -46,11 +48,12 @@ pub async fn handler(
 
     sql.push_str(" ORDER BY symbol");
 
-    tracing::debug!("Executing instruments query: {}", sql);
+    let db_query = BoundQuery::new(sql, params);
 
-    let rows: Vec<InstrumentRow> = state
-        .clickhouse
-        .query(&sql)
+    tracing::debug!("Executing instruments query: {} {:?}", db_query.sql, db_query.params);
+
+    let rows: Vec<InstrumentRow> = db_query
+        .to_query(&state.clickhouse)
         .fetch_all()
         .await?;
 
@@ -81,10 +84,3 @@ pub async fn handler(
 
     Ok(HttpResponse::Ok().json(response))
 }
-
-fn escape_string(s: &str) -> String {
-    s.replace('\'', "''")
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == ' ')
-        .collect()
-}
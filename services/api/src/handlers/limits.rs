@@ -0,0 +1,117 @@
+use actix_web::{web, HttpResponse};
+use std::time::Instant;
+
+use crate::db::clickhouse::fetch_json_rows;
+use crate::db::limits::{delete_limit, get_limit, list_limits, new_limit_id, write_limit};
+use crate::error::ApiError;
+use crate::models::request::{LimitBreachesQuery, RiskLimitSpec};
+use crate::models::response::{LimitBreachesResponse, LimitsResponse, QueryMetadata, RiskLimit};
+use crate::query::limits::{evaluate, evaluation_query, validate_limit};
+use crate::query::params::validate_date;
+use crate::AppState;
+
+pub async fn list(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let limits = list_limits(&state.clickhouse).await?;
+    Ok(HttpResponse::Ok().json(LimitsResponse {
+        count: limits.len(),
+        limits,
+    }))
+}
+
+pub async fn get(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = find_limit(&state, &path).await?;
+    Ok(HttpResponse::Ok().json(limit))
+}
+
+pub async fn create(
+    state: web::Data<AppState>,
+    body: web::Json<RiskLimitSpec>,
+) -> Result<HttpResponse, ApiError> {
+    let spec = body.into_inner();
+    validate_limit(&spec)?;
+
+    let limit = RiskLimit {
+        limit_id: new_limit_id(&state.clickhouse).await?,
+        spec,
+    };
+    write_limit(&state.clickhouse, &limit).await?;
+
+    Ok(HttpResponse::Created().json(limit))
+}
+
+pub async fn update(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<RiskLimitSpec>,
+) -> Result<HttpResponse, ApiError> {
+    let spec = body.into_inner();
+    validate_limit(&spec)?;
+
+    let limit_id = find_limit(&state, &path).await?.limit_id;
+    let limit = RiskLimit { limit_id, spec };
+    write_limit(&state.clickhouse, &limit).await?;
+
+    Ok(HttpResponse::Ok().json(limit))
+}
+
+pub async fn delete(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = find_limit(&state, &path).await?;
+    delete_limit(&state.clickhouse, &limit.limit_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Every limit evaluated against the `trade_date` aggregates, most utilised first.
+pub async fn breaches(
+    state: web::Data<AppState>,
+    query: web::Query<LimitBreachesQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+    validate_date("trade_date", &query.trade_date)?;
+
+    let limits = list_limits(&state.clickhouse).await?;
+    let mut data = Vec::new();
+    for limit in &limits {
+        let bound = evaluation_query(limit, &query.trade_date)?;
+        tracing::debug!(
+            "Evaluating limit {}: {} {:?}",
+            limit.limit_id,
+            bound.sql,
+            bound.params
+        );
+        let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+        data.extend(
+            evaluate(limit, rows)
+                .into_iter()
+                .filter(|u| query.include_ok || u.breached),
+        );
+    }
+    data.sort_by(|a, b| b.utilisation_pct.total_cmp(&a.utilisation_pct));
+
+    let response = LimitBreachesResponse {
+        trade_date: query.trade_date.clone(),
+        limits_evaluated: limits.len(),
+        metadata: QueryMetadata {
+            total_rows: data.len() as u64,
+            returned_rows: data.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some("pivot.trades_1d".to_string()),
+            next_cursor: None,
+        },
+        data,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn find_limit(state: &AppState, limit_id: &str) -> Result<RiskLimit, ApiError> {
+    get_limit(&state.clickhouse, limit_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Limit '{}' not found", limit_id)))
+}
@@ -4,3 +4,9 @@ pub mod instruments;
 pub mod constituents;
 pub mod exposure;
 pub mod pnl;
+pub mod timeseries;
+pub mod concentration;
+pub mod limits;
+pub mod positions;
+pub mod stress;
+pub mod var;
@@ -4,7 +4,13 @@ use std::time::Instant;
 
 use crate::error::ApiError;
 use crate::models::request::PivotRequest;
-use crate::models::response::{PivotResponse, PivotRow, QueryMetadata};
+use crate::db::clickhouse::fetch_json_rows;
+use crate::models::response::{
+    ColumnAxis, MetricComparison, MetricValue, PivotResponse, PivotRow, QueryMetadata,
+};
+use crate::query::builder::{
+    parse_cell_alias, parse_comparison_alias, ComparisonPart, GROUPING_ID_ALIAS,
+};
 use crate::query::PivotQueryBuilder;
 use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
 use crate::AppState;
@@ -30,16 +36,51 @@ pub async fn handler(
     }
 
     // Build the query
-    let builder = PivotQueryBuilder::from_request(&request)?;
-    let sql = builder.build();
+    let mut builder = PivotQueryBuilder::from_request(&request)?;
+
+    // Cross-tab: resolve the column headers before building the main query
+    let columns = match builder.build_header_query() {
+        Some(header_query) => {
+            tracing::debug!(
+                "Executing pivot header query: {} {:?}",
+                header_query.sql,
+                header_query.params
+            );
+            let rows = fetch_json_rows(&state.config.clickhouse.url, &header_query).await?;
+            let headers = rows
+                .iter()
+                .map(|row| {
+                    builder
+                        .column_dimensions()
+                        .iter()
+                        .map(|d| json_to_header(row.get(d.to_column())))
+                        .collect()
+                })
+                .collect();
+            let truncated = builder.set_column_headers(headers);
+
+            Some(ColumnAxis {
+                dimensions: builder
+                    .column_dimensions()
+                    .iter()
+                    .map(|d| d.to_column().to_string())
+                    .collect(),
+                headers: builder.column_headers().to_vec(),
+                truncated,
+            })
+        }
+        None => None,
+    };
 
-    tracing::debug!("Executing pivot query: {}", sql);
+    let query = builder.build();
+
+    tracing::debug!("Executing pivot query: {} {:?}", query.sql, query.params);
 
     // Execute query and get raw JSON response
-    let json_query = format!("{} FORMAT JSONEachRow", sql);
-    let raw_response = state
-        .clickhouse
-        .query(&json_query)
+    let json_query = format!("{} FORMAT JSONEachRow", query.sql);
+    let raw_response = query
+        .params
+        .apply(state.clickhouse.query(&json_query))
         .fetch_all::<String>()
         .await;
 
@@ -50,24 +91,7 @@ pub async fn handler(
                 .filter_map(|json_str| {
                     serde_json::from_str::<HashMap<String, serde_json::Value>>(&json_str).ok()
                 })
-                .map(|row| {
-                    let mut dimensions = HashMap::new();
-                    let mut metrics = HashMap::new();
-
-                    for (key, value) in row {
-                        if key.starts_with("total_") || key.ends_with("_count") || key == "avg_price" {
-                            if let Some(num) = value.as_f64() {
-                                metrics.insert(key, num);
-                            } else if let Some(num) = value.as_i64() {
-                                metrics.insert(key, num as f64);
-                            }
-                        } else {
-                            dimensions.insert(key, value);
-                        }
-                    }
-
-                    PivotRow { dimensions, metrics }
-                })
+                .map(|row| to_pivot_row(&builder, row))
                 .collect()
         }
         Err(_) => {
@@ -76,7 +100,8 @@ pub async fn handler(
             let client = reqwest::Client::new();
             let response = client
                 .post(&http_query)
-                .body(sql.clone())
+                .query(&query.params.to_http_pairs())
+                .body(query.sql.clone())
                 .send()
                 .await
                 .map_err(|e| ApiError::Database(e.to_string()))?;
@@ -91,36 +116,43 @@ pub async fn handler(
                 .filter_map(|line| {
                     serde_json::from_str::<HashMap<String, serde_json::Value>>(line).ok()
                 })
-                .map(|row| {
-                    let mut dimensions = HashMap::new();
-                    let mut metrics = HashMap::new();
-
-                    for (key, value) in row {
-                        if key.starts_with("total_") || key.ends_with("_count") || key == "avg_price" {
-                            if let Some(num) = value.as_f64() {
-                                metrics.insert(key, num);
-                            } else if let Some(num) = value.as_i64() {
-                                metrics.insert(key, num as f64);
-                            }
-                        } else {
-                            dimensions.insert(key, value);
-                        }
-                    }
-
-                    PivotRow { dimensions, metrics }
-                })
+                .map(|row| to_pivot_row(&builder, row))
                 .collect()
         }
     };
 
+    // True group count, independent of LIMIT/OFFSET
+    let count_query = builder.build_count_query();
+    tracing::debug!(
+        "Executing pivot count query: {} {:?}",
+        count_query.sql,
+        count_query.params
+    );
+    let total_rows = fetch_json_rows(&state.config.clickhouse.url, &count_query)
+        .await?
+        .first()
+        .and_then(|row| row.get("total_rows"))
+        .and_then(json_to_f64)
+        .map(|n| n as u64)
+        .unwrap_or(data.len() as u64);
+
+    let next_cursor = if data.len() == request.limit as usize {
+        data.last().and_then(|row| builder.next_cursor(row))
+    } else {
+        None
+    };
+
     let response = PivotResponse {
         metadata: QueryMetadata {
-            total_rows: data.len() as u64,
+            total_rows,
             returned_rows: data.len(),
             query_time_ms: start.elapsed().as_millis() as u64,
             cached: false,
+            source_table: Some(builder.source().name().to_string()),
+            next_cursor,
         },
         data,
+        columns,
     };
 
     // Cache the response
@@ -132,3 +164,81 @@ pub async fn handler(
 
     Ok(HttpResponse::Ok().json(response))
 }
+
+fn to_pivot_row(builder: &PivotQueryBuilder, row: HashMap<String, serde_json::Value>) -> PivotRow {
+    let mut dimensions = HashMap::new();
+    let mut metrics = HashMap::new();
+    let mut cells = vec![HashMap::new(); builder.column_headers().len()];
+    let mut comparison: HashMap<String, MetricComparison> = HashMap::new();
+    let mut grouping_id = 0;
+
+    for (key, value) in row {
+        if let Some((index, alias)) = parse_cell_alias(&key) {
+            let num = json_to_metric(&value, builder.is_integer_metric(alias));
+            if let (Some(cell), Some(num)) = (cells.get_mut(index), num) {
+                cell.insert(alias.to_string(), num);
+            }
+        } else if key == GROUPING_ID_ALIAS {
+            // UInt64 values are quoted in JSONEachRow output by default
+            grouping_id = value
+                .as_u64()
+                .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                .unwrap_or(0);
+        } else if builder.is_metric_alias(&key) {
+            if let Some(num) = json_to_metric(&value, builder.is_integer_metric(&key)) {
+                if builder.is_comparison() {
+                    comparison.entry(key.clone()).or_default().current = Some(num.as_f64());
+                }
+                metrics.insert(key, num);
+            }
+        } else if let Some((alias, part)) = parse_comparison_alias(&key)
+            .filter(|(alias, _)| builder.is_comparison() && builder.is_metric_alias(alias))
+        {
+            let entry = comparison.entry(alias.to_string()).or_default();
+            let num = json_to_f64(&value);
+            match part {
+                ComparisonPart::Prior => entry.prior = num,
+                ComparisonPart::Change => entry.change = num,
+                ComparisonPart::ChangePct => entry.change_pct = num,
+            }
+        } else {
+            dimensions.insert(key, value);
+        }
+    }
+
+    // Subtotal rows carry the column default ('' or 0) for aggregated dimensions;
+    // report them as null so they can't be mistaken for real values.
+    let aggregated = builder.aggregated_dimensions(grouping_id);
+    for column in &aggregated {
+        dimensions.insert(column.clone(), serde_json::Value::Null);
+    }
+
+    PivotRow { dimensions, metrics, cells, aggregated, comparison }
+}
+
+/// Numeric value of a metric; 64-bit integers (e.g. `count()`) arrive quoted.
+fn json_to_f64(value: &serde_json::Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// Typed metric value; integer metrics fall back to a float if ClickHouse returns a fraction.
+fn json_to_metric(value: &serde_json::Value, integer: bool) -> Option<MetricValue> {
+    let as_integer = value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+    match as_integer {
+        Some(n) if integer => Some(MetricValue::Integer(n)),
+        _ => json_to_f64(value).map(MetricValue::Float),
+    }
+}
+
+/// Header values are compared as `toString(column)`, so render them the same way.
+fn json_to_header(value: Option<&serde_json::Value>) -> String {
+    match value {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
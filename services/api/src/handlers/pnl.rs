@@ -2,9 +2,17 @@ use actix_web::{web, HttpResponse};
 use std::collections::HashMap;
 use std::time::Instant;
 
+use crate::db::clickhouse::fetch_json_rows;
 use crate::error::ApiError;
-use crate::models::request::PnlQuery;
-use crate::models::response::{PnlResponse, PnlRow, QueryMetadata};
+use crate::models::request::{PnlAttributionQuery, PnlQuery};
+use crate::models::response::{
+    PnlAttributionResponse, PnlAttributionRow, PnlResponse, PnlRow, QueryMetadata,
+};
+use crate::query::attribution::{attribution_query, ATTRIBUTION_COLUMNS};
+use crate::query::fx::{converted_source, validate_currency};
+use crate::query::params::{validate_date, QueryParams};
+use crate::query::window::lookback_start;
+use crate::query::{Metric, WindowKind, WindowMetric};
 use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
 use crate::AppState;
 
@@ -27,23 +35,11 @@ pub async fn handler(
 ) -> Result<HttpResponse, ApiError> {
     let start = Instant::now();
 
-    // Parse group_by from comma-separated string
-    let group_by_cols: Vec<&str> = query.group_by.split(',').map(|s| s.trim()).collect();
-
-    // Validate all group_by columns
-    for col in &group_by_cols {
-        if !ALLOWED_GROUP_BY.contains(col) {
-            return Err(ApiError::QueryValidation(format!(
-                "Invalid group_by column: '{}'. Allowed values: {:?}",
-                col, ALLOWED_GROUP_BY
-            )));
-        }
-    }
-
-    if group_by_cols.is_empty() || (group_by_cols.len() == 1 && group_by_cols[0].is_empty()) {
-        return Err(ApiError::QueryValidation(
-            "At least one group_by column is required".to_string(),
-        ));
+    let group_by_cols = parse_group_by(&query.group_by)?;
+    validate_date("trade_date", &query.trade_date)?;
+    let windows = parse_windows(&query.windows)?;
+    if let Some(ref currency) = query.reporting_currency {
+        validate_currency(currency)?;
     }
 
     // Check cache first
@@ -61,25 +57,71 @@ pub async fn handler(
 
     // Build query
     let group_cols = group_by_cols.join(", ");
-    let sql = format!(
-        "SELECT {}, sum(pnl) AS total_pnl, sum(notional) AS total_notional, count() AS trade_count
-         FROM pivot.trades_1d
-         WHERE trade_date = '{}'
-         GROUP BY {}
-         ORDER BY total_pnl DESC
-         LIMIT 100",
-        group_cols,
-        escape_string(&query.trade_date),
-        group_cols
-    );
-
-    tracing::debug!("Executing P&L query: {}", sql);
+    let mut params = QueryParams::new();
+    let trade_date = params.bind("Date", &query.trade_date);
+    let source = match query.reporting_currency {
+        Some(ref currency) => converted_source("pivot.trades_1d", currency, &mut params),
+        None => "pivot.trades_1d".to_string(),
+    };
+    let sql = if windows.is_empty() {
+        format!(
+            "SELECT {}, sum(pnl) AS total_pnl, sum(notional) AS total_notional, count() AS trade_count
+             FROM {}
+             WHERE trade_date = {}
+             GROUP BY {}
+             ORDER BY total_pnl DESC
+             LIMIT 100",
+            group_cols, source, trade_date, group_cols
+        )
+    } else {
+        // Aggregate per day back to the start of the longest window, then keep the requested day
+        let parents = &group_by_cols[..group_by_cols.len() - 1];
+        let rank_partition: Vec<&str> = parents.iter().copied().chain(["trade_date"]).collect();
+        let window_cols: Vec<String> = windows
+            .iter()
+            .map(|w| {
+                let partition = if w.kind.is_cumulative() {
+                    &group_by_cols[..]
+                } else {
+                    &rank_partition[..]
+                };
+                format!("{} AS {}", w.to_sql("sum(pnl)", partition, "trade_date"), w.alias())
+            })
+            .collect();
+        let aliases: Vec<String> = windows.iter().map(|w| w.alias()).collect();
+
+        format!(
+            "SELECT {}, total_pnl, total_notional, trade_count, {}
+             FROM (
+                 SELECT {}, trade_date, sum(pnl) AS total_pnl, sum(notional) AS total_notional,
+                        count() AS trade_count, {}
+                 FROM {}
+                 WHERE trade_date >= {} AND trade_date <= {}
+                 GROUP BY {}, trade_date
+             )
+             WHERE trade_date = {}
+             ORDER BY total_pnl DESC
+             LIMIT 100",
+            group_cols,
+            aliases.join(", "),
+            group_cols,
+            window_cols.join(", "),
+            source,
+            lookback_start(&windows, &trade_date),
+            trade_date,
+            group_cols,
+            trade_date
+        )
+    };
+
+    tracing::debug!("Executing P&L query: {} {:?}", sql, params);
 
     // Use HTTP client to get JSON response
     let http_url = format!("{}/?default_format=JSONEachRow", state.config.clickhouse.url);
     let client = reqwest::Client::new();
     let response = client
         .post(&http_url)
+        .query(&params.to_http_pairs())
         .body(sql)
         .send()
         .await
@@ -101,17 +143,32 @@ pub async fn handler(
             let mut total_pnl = 0.0;
             let mut total_notional = 0.0;
             let mut trade_count = 0u64;
+            let mut window_values = HashMap::new();
 
             for (key, value) in row {
                 match key.as_str() {
                     "total_pnl" => total_pnl = value.as_f64().unwrap_or(0.0),
                     "total_notional" => total_notional = value.as_f64().unwrap_or(0.0),
-                    "trade_count" => trade_count = value.as_u64().unwrap_or(0),
+                    // count() and rank() are UInt64, which JSONEachRow quotes
+                    "trade_count" => {
+                        trade_count = value
+                            .as_u64()
+                            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                            .unwrap_or(0)
+                    }
+                    _ if windows.iter().any(|w| w.alias() == key) => {
+                        let num = value
+                            .as_f64()
+                            .or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+                        if let Some(num) = num {
+                            window_values.insert(key, num);
+                        }
+                    }
                     _ => { groups.insert(key, value); }
                 }
             }
 
-            PnlRow { groups, total_pnl, total_notional, trade_count }
+            PnlRow { groups, total_pnl, total_notional, trade_count, windows: window_values }
         })
         .collect();
 
@@ -121,6 +178,8 @@ pub async fn handler(
             returned_rows: data.len(),
             query_time_ms: start.elapsed().as_millis() as u64,
             cached: false,
+            source_table: Some("pivot.trades_1d".to_string()),
+            next_cursor: None,
         },
         data,
     };
@@ -135,8 +194,123 @@ pub async fn handler(
     Ok(HttpResponse::Ok().json(response))
 }
 
-fn escape_string(s: &str) -> String {
-    s.chars()
-        .filter(|c| c.is_ascii_digit() || *c == '-')
-        .collect()
+/// P&L of `trade_date` explained by Greek, cost and unexplained buckets.
+pub async fn attribution(
+    state: web::Data<AppState>,
+    query: web::Query<PnlAttributionQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+
+    let group_by_cols = parse_group_by(&query.group_by)?;
+    validate_date("trade_date", &query.trade_date)?;
+
+    // Check cache first
+    let cache_key = generate_cache_key("pnl_attribution", &serde_json::to_string(&query.0)?);
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<PnlAttributionResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let bound = attribution_query(&group_by_cols, &query.trade_date);
+    tracing::debug!("Executing P&L attribution query: {} {:?}", bound.sql, bound.params);
+
+    let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+    let data: Vec<PnlAttributionRow> = rows
+        .into_iter()
+        .map(|mut row| {
+            let mut bucket = |key: &str| -> f64 {
+                row.remove(key)
+                    .and_then(|v| v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+                    .unwrap_or(0.0)
+            };
+            let buckets: Vec<f64> = ATTRIBUTION_COLUMNS.iter().map(|c| bucket(c)).collect();
+            // count() is a UInt64, which JSONEachRow quotes
+            let trade_count = bucket("trade_count") as u64;
+
+            PnlAttributionRow {
+                groups: row,
+                total_pnl: buckets[0],
+                delta_pnl: buckets[1],
+                gamma_pnl: buckets[2],
+                vega_pnl: buckets[3],
+                theta_pnl: buckets[4],
+                fees_drag: buckets[5],
+                slippage_drag: buckets[6],
+                unexplained_pnl: buckets[7],
+                trade_count,
+            }
+        })
+        .collect();
+
+    let response = PnlAttributionResponse {
+        trade_date: query.trade_date.clone(),
+        metadata: QueryMetadata {
+            total_rows: data.len() as u64,
+            returned_rows: data.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some("pivot.trades_1d+pivot.price_history".to_string()),
+            next_cursor: None,
+        },
+        data,
+    };
+
+    // Cache the response
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Parses and validates the comma-separated `group_by` parameter against the whitelist.
+fn parse_group_by(group_by: &str) -> Result<Vec<&str>, ApiError> {
+    let group_by_cols: Vec<&str> = group_by.split(',').map(|s| s.trim()).collect();
+
+    // Validate all group_by columns
+    for col in &group_by_cols {
+        if !ALLOWED_GROUP_BY.contains(col) {
+            return Err(ApiError::QueryValidation(format!(
+                "Invalid group_by column: '{}'. Allowed values: {:?}",
+                col, ALLOWED_GROUP_BY
+            )));
+        }
+    }
+
+    if group_by_cols.is_empty() || (group_by_cols.len() == 1 && group_by_cols[0].is_empty()) {
+        return Err(ApiError::QueryValidation(
+            "At least one group_by column is required".to_string(),
+        ));
+    }
+
+    Ok(group_by_cols)
+}
+
+/// Parses the comma-separated `windows` parameter into P&L window metrics.
+fn parse_windows(windows: &str) -> Result<Vec<WindowMetric>, ApiError> {
+    let mut parsed: Vec<WindowMetric> = Vec::new();
+
+    for name in windows.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let kind = WindowKind::from_name(name)
+            .filter(|k| *k != WindowKind::Running)
+            .ok_or_else(|| {
+                ApiError::QueryValidation(format!(
+                    "Invalid window: '{}'. Allowed values: [\"mtd\", \"ytd\", \"rank\"]",
+                    name
+                ))
+            })?;
+        let window = WindowMetric { kind, metric: Metric::Pnl };
+        if !parsed.contains(&window) {
+            parsed.push(window);
+        }
+    }
+
+    Ok(parsed)
 }
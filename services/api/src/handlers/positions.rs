@@ -0,0 +1,61 @@
+use actix_web::{web, HttpResponse};
+use std::time::Instant;
+
+use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
+use crate::db::clickhouse::fetch_json_rows;
+use crate::error::ApiError;
+use crate::models::request::PositionsQuery;
+use crate::models::response::{PositionsResponse, QueryMetadata};
+use crate::query::positions::PositionsQueryBuilder;
+use crate::AppState;
+
+pub async fn handler(
+    state: web::Data<AppState>,
+    query: web::Query<PositionsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+
+    let builder = PositionsQueryBuilder::from_query(&query)?;
+
+    // Check cache first
+    let cache_key = generate_cache_key("positions", &serde_json::to_string(&query.0)?);
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<PositionsResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let bound = builder.build();
+    tracing::debug!("Executing positions query: {} {:?}", bound.sql, bound.params);
+
+    let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+    let positions = builder.net(rows);
+
+    let response = PositionsResponse {
+        as_of: query.as_of.clone(),
+        dimension: query.level.to_column().to_string(),
+        summary: positions.summary,
+        metadata: QueryMetadata {
+            total_rows: positions.total as u64,
+            returned_rows: positions.data.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some("pivot.positions_1d".to_string()),
+            next_cursor: None,
+        },
+        data: positions.data,
+    };
+
+    // Cache the response
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,186 @@
+use actix_web::{web, HttpResponse};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
+use crate::db::clickhouse::fetch_json_rows;
+use crate::db::shock_sets::{get_shock_set, list_shock_sets, write_shock_set};
+use crate::error::ApiError;
+use crate::models::request::{ShockSetSpec, StressRequest};
+use crate::models::response::{
+    QueryMetadata, ShockSet, ShockSetsResponse, StressResponse, StressRow,
+};
+use crate::query::stress::{validate_shocks, ScopedShock, StressQueryBuilder};
+use crate::AppState;
+
+pub async fn handler(
+    state: web::Data<AppState>,
+    body: web::Json<StressRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+    let request = body.into_inner();
+
+    let shocks = resolve_shocks(&state, &request).await?;
+    let builder = StressQueryBuilder::new(&request, shocks.clone())?;
+
+    // Resolved shocks are part of the key so an edited set is not served stale
+    let cache_key = generate_cache_key(
+        "stress",
+        &format!("{}{:?}", serde_json::to_string(&request)?, shocks),
+    );
+    if !request.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<StressResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let bound = builder.build()?;
+    tracing::debug!("Executing stress query: {} {:?}", bound.sql, bound.params);
+
+    let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+    let data: Vec<StressRow> = rows
+        .into_iter()
+        .map(|row| to_stress_row(&builder, row))
+        .collect();
+
+    let response = StressResponse {
+        metadata: QueryMetadata {
+            total_rows: data.len() as u64,
+            returned_rows: data.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some("pivot.trades_1d".to_string()),
+            next_cursor: None,
+        },
+        data,
+    };
+
+    if !request.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn list_sets(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let shock_sets = list_shock_sets(&state.clickhouse).await?;
+    Ok(HttpResponse::Ok().json(ShockSetsResponse {
+        count: shock_sets.len(),
+        shock_sets,
+    }))
+}
+
+pub async fn get_set(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let set = find_shock_set(&state, &path).await?;
+    Ok(HttpResponse::Ok().json(set))
+}
+
+/// Creates or replaces the shock set named in the path.
+pub async fn put_set(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<ShockSetSpec>,
+) -> Result<HttpResponse, ApiError> {
+    let name = path.into_inner();
+    if name.trim().is_empty() {
+        return Err(ApiError::QueryValidation(
+            "Shock set name must not be empty".to_string(),
+        ));
+    }
+    let spec = body.into_inner();
+    validate_shocks(&spec.shocks)?;
+
+    let set = ShockSet {
+        name,
+        description: spec.description,
+        shocks: spec.shocks,
+    };
+    write_shock_set(&state.clickhouse, &set).await?;
+
+    Ok(HttpResponse::Ok().json(set))
+}
+
+/// Shocks the request applies: inline, one stored set, or every stored set scoped
+/// to the rows whose `scenario` carries its name.
+async fn resolve_shocks(
+    state: &AppState,
+    request: &StressRequest,
+) -> Result<Vec<ScopedShock>, ApiError> {
+    let unscoped = |shocks: Vec<_>| -> Vec<ScopedShock> {
+        shocks
+            .into_iter()
+            .map(|shock| ScopedShock { scenario: None, shock })
+            .collect()
+    };
+
+    if let Some(name) = &request.shock_set {
+        return Ok(unscoped(find_shock_set(state, name).await?.shocks));
+    }
+    if request.by_row_scenario {
+        let sets = list_shock_sets(&state.clickhouse).await?;
+        return Ok(sets
+            .into_iter()
+            .flat_map(|set| {
+                let name = set.name;
+                set.shocks.into_iter().map(move |shock| ScopedShock {
+                    scenario: Some(name.clone()),
+                    shock,
+                })
+            })
+            .collect());
+    }
+    Ok(unscoped(request.shocks.clone()))
+}
+
+async fn find_shock_set(state: &AppState, name: &str) -> Result<ShockSet, ApiError> {
+    get_shock_set(&state.clickhouse, name)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Shock set '{}' not found", name)))
+}
+
+fn to_stress_row(builder: &StressQueryBuilder, mut row: HashMap<String, Value>) -> StressRow {
+    let mut take = |key: &str| -> f64 {
+        row.remove(key)
+            .and_then(|v| v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+            .unwrap_or(0.0)
+    };
+    let (base_pnl, delta_pnl, gamma_pnl, vega_pnl, rho_pnl, theta_pnl, stress_pnl) = (
+        take("base_pnl"),
+        take("delta_pnl"),
+        take("gamma_pnl"),
+        take("vega_pnl"),
+        take("rho_pnl"),
+        take("theta_pnl"),
+        take("stress_pnl"),
+    );
+
+    let dimensions = builder
+        .dimensions()
+        .iter()
+        .map(|d| {
+            let column = d.to_column();
+            (column.to_string(), row.remove(column).unwrap_or(Value::Null))
+        })
+        .collect();
+
+    StressRow {
+        dimensions,
+        base_pnl,
+        delta_pnl,
+        gamma_pnl,
+        vega_pnl,
+        rho_pnl,
+        theta_pnl,
+        stress_pnl,
+    }
+}
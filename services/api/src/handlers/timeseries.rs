@@ -0,0 +1,61 @@
+use actix_web::{web, HttpResponse};
+use std::time::Instant;
+
+use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
+use crate::db::clickhouse::fetch_json_rows;
+use crate::error::ApiError;
+use crate::models::request::TimeseriesQuery;
+use crate::models::response::{QueryMetadata, TimeseriesResponse};
+use crate::query::timeseries::TimeseriesQueryBuilder;
+use crate::AppState;
+
+pub async fn handler(
+    state: web::Data<AppState>,
+    query: web::Query<TimeseriesQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+
+    let builder = TimeseriesQueryBuilder::from_query(&query)?;
+
+    // Check cache first
+    let cache_key = generate_cache_key("timeseries", &serde_json::to_string(&query.0)?);
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<TimeseriesResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let bound = builder.build();
+    tracing::debug!("Executing timeseries query: {} {:?}", bound.sql, bound.params);
+
+    let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+    // Both counts are in points, one per series and bucket, as the rows come back
+    let total_rows = rows.len() as u64;
+    let data = builder.into_series(rows);
+    let returned_rows = data.iter().map(|s| s.points.len()).sum();
+
+    let response = TimeseriesResponse {
+        metadata: QueryMetadata {
+            total_rows,
+            returned_rows,
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some(builder.source().name().to_string()),
+            next_cursor: None,
+        },
+        data,
+    };
+
+    // Cache the response
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,66 @@
+use actix_web::{web, HttpResponse};
+use std::time::Instant;
+
+use crate::cache::redis::{generate_cache_key, get_cached, set_cached};
+use crate::db::clickhouse::fetch_json_rows;
+use crate::error::ApiError;
+use crate::models::request::VarQuery;
+use crate::models::response::{QueryMetadata, VarResponse};
+use crate::query::var::VarQueryBuilder;
+use crate::query::SourceTable;
+use crate::AppState;
+
+pub async fn handler(
+    state: web::Data<AppState>,
+    query: web::Query<VarQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let start = Instant::now();
+
+    let builder = VarQueryBuilder::from_query(&query)?;
+
+    // Check cache first
+    let cache_key = generate_cache_key("var", &serde_json::to_string(&query.0)?);
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+
+        if let Ok(Some(cached)) = get_cached::<VarResponse>(&mut redis, &cache_key).await {
+            let mut response = cached;
+            response.metadata.cached = true;
+            response.metadata.query_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let bound = builder.build();
+    tracing::debug!("Executing VaR query: {} {:?}", bound.sql, bound.params);
+
+    let rows = fetch_json_rows(&state.config.clickhouse.url, &bound).await?;
+    let estimate = builder.estimate(rows);
+
+    let response = VarResponse {
+        trade_date: query.trade_date.clone(),
+        dimension: query.group_by.to_column().to_string(),
+        confidence: query.confidence,
+        currency: builder.currency().to_string(),
+        scenarios: estimate.scenarios,
+        var: estimate.var,
+        expected_shortfall: estimate.expected_shortfall,
+        metadata: QueryMetadata {
+            total_rows: estimate.groups.len() as u64,
+            returned_rows: estimate.groups.len(),
+            query_time_ms: start.elapsed().as_millis() as u64,
+            cached: false,
+            source_table: Some(format!("{}+pivot.price_history", SourceTable::LookThrough.name())),
+            next_cursor: None,
+        },
+        data: estimate.groups,
+    };
+
+    // Cache the response
+    if !query.cache_bypass && state.config.cache.enabled {
+        let mut redis = state.redis.clone();
+        let _ = set_cached(&mut redis, &cache_key, &response, state.config.cache.ttl_seconds).await;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -16,8 +16,27 @@ fn configure_routes(cfg: &mut web::ServiceConfig) {
                 .route("/pivot", web::post().to(handlers::pivot::handler))
                 .route("/instruments", web::get().to(handlers::instruments::handler))
                 .route("/constituents", web::get().to(handlers::constituents::handler))
+                .route(
+                    "/constituents/diff",
+                    web::get().to(handlers::constituents::diff_handler),
+                )
                 .route("/exposure", web::get().to(handlers::exposure::handler))
-                .route("/pnl", web::get().to(handlers::pnl::handler)),
+                .route("/pnl", web::get().to(handlers::pnl::handler))
+                .route("/pnl/attribution", web::get().to(handlers::pnl::attribution))
+                .route("/timeseries", web::get().to(handlers::timeseries::handler))
+                .route("/concentration", web::get().to(handlers::concentration::handler))
+                .route("/limits", web::get().to(handlers::limits::list))
+                .route("/limits", web::post().to(handlers::limits::create))
+                .route("/limits/breaches", web::get().to(handlers::limits::breaches))
+                .route("/limits/{limit_id}", web::get().to(handlers::limits::get))
+                .route("/limits/{limit_id}", web::put().to(handlers::limits::update))
+                .route("/limits/{limit_id}", web::delete().to(handlers::limits::delete))
+                .route("/stress", web::post().to(handlers::stress::handler))
+                .route("/stress/shock-sets", web::get().to(handlers::stress::list_sets))
+                .route("/stress/shock-sets/{name}", web::get().to(handlers::stress::get_set))
+                .route("/stress/shock-sets/{name}", web::put().to(handlers::stress::put_set))
+                .route("/var", web::get().to(handlers::var::handler))
+                .route("/positions", web::get().to(handlers::positions::handler)),
         );
 }
 
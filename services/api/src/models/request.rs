@@ -1,18 +1,53 @@
 use serde::{Deserialize, Serialize};
-use crate::query::{Dimension, Metric};
+use crate::query::{Dimension, Metric, WindowMetric};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PivotRequest {
+    /// Row dimensions.
+    #[serde(alias = "rows")]
     pub dimensions: Vec<Dimension>,
+    /// Column dimensions; when set the response is a cross-tab with one cell per column header.
+    #[serde(default)]
+    pub columns: Vec<Dimension>,
+    #[serde(default = "default_max_columns")]
+    pub max_columns: u32,
     pub metrics: Vec<Metric>,
+    /// User-defined formulas, returned alongside `metrics` under their `name`.
+    #[serde(default)]
+    pub calculated_metrics: Vec<CalculatedMetric>,
+    /// Running, period-to-date and rank windows over metrics.
+    #[serde(default)]
+    pub window_metrics: Vec<WindowMetric>,
     #[serde(default)]
     pub filters: PivotFilters,
+    /// Post-aggregation filters on metric values, AND-ed together.
     #[serde(default)]
-    pub sort: Option<SortSpec>,
+    pub having: Vec<MetricFilter>,
+    /// Sort keys in priority order; a single object is accepted for backward compatibility.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub sort: Vec<SortSpec>,
     #[serde(default = "default_limit")]
     pub limit: u32,
     #[serde(default)]
     pub offset: u32,
+    /// Opaque keyset cursor from a previous page's `metadata.next_cursor`; replaces `offset`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub totals: TotalsMode,
+    /// Keep only the top `n` values of one row dimension per parent group.
+    #[serde(default)]
+    pub top_n: Option<TopN>,
+    /// Prior period to compare the `filters` trade date (or range) against.
+    #[serde(default)]
+    pub compare: Option<ComparePeriod>,
+    /// Split ETF/ETC trades into their constituents at query time, using the
+    /// `pivot.constituents` weights in effect on each trade date.
+    #[serde(default)]
+    pub look_through: bool,
+    /// Convert monetary metrics into this currency at each trade date's rate.
+    #[serde(default)]
+    pub reporting_currency: Option<String>,
     #[serde(default)]
     pub cache_bypass: bool,
 }
@@ -21,11 +56,22 @@ impl Default for PivotRequest {
     fn default() -> Self {
         Self {
             dimensions: vec![],
+            columns: vec![],
+            max_columns: default_max_columns(),
             metrics: vec![],
+            calculated_metrics: vec![],
+            window_metrics: vec![],
             filters: PivotFilters::default(),
-            sort: None,
+            having: vec![],
+            sort: vec![],
             limit: default_limit(),
             offset: 0,
+            cursor: None,
+            totals: TotalsMode::None,
+            top_n: None,
+            compare: None,
+            look_through: false,
+            reporting_currency: None,
             cache_bypass: false,
         }
     }
@@ -35,6 +81,66 @@ fn default_limit() -> u32 {
     100
 }
 
+fn default_max_columns() -> u32 {
+    50
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalculatedMetric {
+    pub name: String,
+    /// Formula over `trades_1d` measure columns, e.g. `(pnl - fees) / notional * 10000`.
+    pub expression: String,
+}
+
+/// A condition on an aggregated metric, or an AND/OR group of them.
+///
+/// ```json
+/// {"or": [{"metric": "total_pnl", "op": "gt", "value": 1000000, "abs": true},
+///         {"metric": "trade_count", "op": "gte", "value": 10}]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricFilter {
+    And { and: Vec<MetricFilter> },
+    Or { or: Vec<MetricFilter> },
+    Condition(MetricCondition),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricCondition {
+    /// Alias of a requested metric (e.g. `total_pnl`) or calculated metric name.
+    pub metric: String,
+    pub op: ComparisonOp,
+    pub value: f64,
+    /// Compare the absolute value of the metric.
+    #[serde(default)]
+    pub abs: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl ComparisonOp {
+    pub fn to_sql(&self) -> &'static str {
+        match self {
+            ComparisonOp::Eq => "=",
+            ComparisonOp::Ne => "!=",
+            ComparisonOp::Gt => ">",
+            ComparisonOp::Gte => ">=",
+            ComparisonOp::Lt => "<",
+            ComparisonOp::Lte => "<=",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PivotFilters {
     pub trade_date: Option<String>,
@@ -50,6 +156,141 @@ pub struct PivotFilters {
     pub book: Option<Vec<String>>,
     pub region: Option<Vec<String>>,
     pub country: Option<Vec<String>>,
+    /// Generic predicates over any dimension or measure column, AND-ed with the fields above.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub predicates: Vec<Predicate>,
+}
+
+impl PivotFilters {
+    /// All filters as predicates: the legacy per-field filters followed by `predicates`.
+    pub fn to_predicates(&self) -> Vec<Predicate> {
+        fn values<T: ToString>(items: &[T]) -> Vec<serde_json::Value> {
+            items
+                .iter()
+                .map(|v| serde_json::Value::String(v.to_string()))
+                .collect()
+        }
+
+        let mut predicates = Vec::new();
+
+        if let Some(ref date) = self.trade_date {
+            predicates.push(Predicate::new(Condition::Eq {
+                dimension: Dimension::TradeDate,
+                value: serde_json::Value::String(date.clone()),
+            }));
+        }
+
+        if let Some(ref range) = self.trade_date_range {
+            predicates.push(Predicate::new(Condition::Between {
+                field: Dimension::TradeDate.to_column().to_string(),
+                min: Some(serde_json::Value::String(range.start.clone())),
+                max: Some(serde_json::Value::String(range.end.clone())),
+            }));
+        }
+
+        if let Some(ref types) = self.exposure_type {
+            let names: Vec<&str> = types.iter().map(|t| t.as_str()).collect();
+            predicates.push(Predicate::new(Condition::In {
+                dimension: Dimension::ExposureType,
+                values: values(&names),
+            }));
+        }
+
+        let id_filters = [
+            (Dimension::PortfolioManagerId, &self.portfolio_manager_id),
+            (Dimension::FundId, &self.fund_id),
+        ];
+        for (dimension, ids) in id_filters {
+            if let Some(ids) = ids {
+                predicates.push(Predicate::new(Condition::In { dimension, values: values(ids) }));
+            }
+        }
+
+        let string_filters = [
+            (Dimension::AssetClass, &self.asset_class),
+            (Dimension::Symbol, &self.symbol),
+            (Dimension::UnderlyingSymbol, &self.underlying_symbol),
+            (Dimension::ParentSymbol, &self.parent_symbol),
+            (Dimension::Desk, &self.desk),
+            (Dimension::Book, &self.book),
+            (Dimension::Region, &self.region),
+            (Dimension::Country, &self.country),
+        ];
+        for (dimension, items) in string_filters {
+            if let Some(items) = items {
+                predicates.push(Predicate::new(Condition::In { dimension, values: values(items) }));
+            }
+        }
+
+        // An empty legacy list has always meant "no filter"
+        predicates.retain(|p| {
+            !matches!(&p.condition, Condition::In { values, .. } if values.is_empty())
+        });
+        predicates.extend(self.predicates.iter().cloned());
+        predicates
+    }
+}
+
+/// A row-level filter, optionally negated.
+///
+/// ```json
+/// {"op": "not_in", "dimension": "strategy", "values": ["Macro"]}
+/// {"op": "between", "field": "notional", "min": 1000000}
+/// {"op": "is_empty", "dimension": "parent_symbol", "negate": true}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Predicate {
+    #[serde(flatten)]
+    pub condition: Condition,
+    #[serde(default)]
+    pub negate: bool,
+}
+
+impl Predicate {
+    pub fn new(condition: Condition) -> Self {
+        Self { condition, negate: false }
+    }
+
+    /// Column the predicate is evaluated against.
+    pub fn field(&self) -> &str {
+        match &self.condition {
+            Condition::Eq { dimension, .. }
+            | Condition::In { dimension, .. }
+            | Condition::NotIn { dimension, .. }
+            | Condition::Prefix { dimension, .. }
+            | Condition::IsEmpty { dimension } => dimension.to_column(),
+            Condition::Between { field, .. } => field,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Condition {
+    Eq {
+        dimension: Dimension,
+        value: serde_json::Value,
+    },
+    In {
+        dimension: Dimension,
+        values: Vec<serde_json::Value>,
+    },
+    NotIn {
+        dimension: Dimension,
+        values: Vec<serde_json::Value>,
+    },
+    /// String dimensions only.
+    Prefix { dimension: Dimension, value: String },
+    /// String dimensions only, e.g. `parent_symbol` on non-constituent rows.
+    IsEmpty { dimension: Dimension },
+    /// Inclusive range over a dimension or a numeric measure column such as `notional`.
+    Between {
+        field: String,
+        #[serde(default)]
+        min: Option<serde_json::Value>,
+        #[serde(default)]
+        max: Option<serde_json::Value>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,12 +301,15 @@ pub struct DateRange {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SortSpec {
+    /// A requested dimension column or metric alias, e.g. `desk` or `total_pnl`.
     pub field: String,
     #[serde(default)]
     pub direction: SortDirection,
+    #[serde(default)]
+    pub nulls: Option<NullsOrder>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SortDirection {
     Asc,
@@ -73,6 +317,92 @@ pub enum SortDirection {
     Desc,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+        Some(OneOrMany::One(item)) => vec![item],
+        Some(OneOrMany::Many(items)) => items,
+        None => vec![],
+    })
+}
+
+/// Which subtotal rows a pivot should include alongside the detail rows.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TotalsMode {
+    #[default]
+    None,
+    /// A single grand total row.
+    Grand,
+    /// Subtotals at every level of the dimension hierarchy, plus the grand total.
+    Rollup,
+    /// Subtotals for every combination of dimensions.
+    Cube,
+}
+
+/// Top-N truncation of a row dimension within each group of the dimensions before it.
+///
+/// ```json
+/// {"dimension": "symbol", "n": 10, "by": "notional", "abs": true}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopN {
+    pub dimension: Dimension,
+    pub n: u32,
+    /// Metric the values are ranked by, largest first.
+    pub by: Metric,
+    /// Rank by magnitude, so large negative values count as large.
+    #[serde(default)]
+    pub abs: bool,
+    /// Fold the remaining values into one [`OTHER_BUCKET`] row per parent group;
+    /// when `false` they are dropped.
+    #[serde(default = "default_true")]
+    pub other: bool,
+}
+
+/// Label of the row that carries the remainder of a top-N dimension.
+pub const OTHER_BUCKET: &str = "Other";
+
+fn default_true() -> bool {
+    true
+}
+
+/// The prior period of a comparison pivot: a single date or an inclusive range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComparePeriod {
+    pub trade_date: Option<String>,
+    pub trade_date_range: Option<DateRange>,
+}
+
+impl ComparePeriod {
+    /// The period as `trade_date` predicates.
+    pub fn to_predicates(&self) -> Vec<Predicate> {
+        PivotFilters {
+            trade_date: self.trade_date.clone(),
+            trade_date_range: self.trade_date_range.clone(),
+            ..Default::default()
+        }
+        .to_predicates()
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ExposureType {
     Direct,
@@ -83,6 +413,18 @@ pub enum ExposureType {
     Constituent,
 }
 
+impl ExposureType {
+    /// Value stored in `trades_1d.exposure_type`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExposureType::Direct => "Direct",
+            ExposureType::Etf => "ETF",
+            ExposureType::Etc => "ETC",
+            ExposureType::Constituent => "Constituent",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InstrumentsQuery {
     pub asset_class: Option<String>,
@@ -93,6 +435,15 @@ pub struct InstrumentsQuery {
 pub struct ConstituentsQuery {
     pub parent_symbol: Option<String>,
     pub constituent_symbol: Option<String>,
+    /// Only the versions in effect on this date; every version when unset.
+    pub as_of: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstituentDiffQuery {
+    pub parent_symbol: String,
+    pub from_date: String,
+    pub to_date: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +453,9 @@ pub struct ExposureQuery {
     pub group_by: String,
     #[serde(default)]
     pub view: ExposureView,
+    /// Convert notional and P&L into this currency at the trade date's rate.
+    #[serde(default)]
+    pub reporting_currency: Option<String>,
     #[serde(default)]
     pub cache_bypass: bool,
 }
@@ -115,7 +469,10 @@ fn default_group_by() -> String {
 pub enum ExposureView {
     #[default]
     TopLevel,
+    /// Constituent rows pre-materialised by `pivot-data-gen --explode-constituents`.
     LookThrough,
+    /// Constituents joined from `pivot.constituents` at query time.
+    QueryLookThrough,
     All,
 }
 
@@ -124,6 +481,22 @@ pub struct PnlQuery {
     pub trade_date: String,
     #[serde(default = "default_pnl_group_by")]
     pub group_by: String,
+    /// Comma-separated P&L windows: `mtd`, `ytd` and `rank` (within the parent groups).
+    #[serde(default)]
+    pub windows: String,
+    /// Convert notional and P&L into this currency at each trade date's rate.
+    #[serde(default)]
+    pub reporting_currency: Option<String>,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PnlAttributionQuery {
+    pub trade_date: String,
+    /// Comma-separated columns, from the same whitelist as `/pnl`.
+    #[serde(default = "default_pnl_group_by")]
+    pub group_by: String,
     #[serde(default)]
     pub cache_bypass: bool,
 }
@@ -131,3 +504,239 @@ pub struct PnlQuery {
 fn default_pnl_group_by() -> String {
     "portfolio_manager_id".to_string()
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeseriesQuery {
+    /// First trade date of the series.
+    pub trade_date: String,
+    /// Last trade date, inclusive; defaults to `trade_date`.
+    #[serde(default)]
+    pub end_date: Option<String>,
+    #[serde(default)]
+    pub interval: TimeInterval,
+    /// Comma-separated dimensions, one series per distinct combination. Empty for a single series.
+    #[serde(default)]
+    pub group_by: String,
+    #[serde(default = "default_timeseries_metric")]
+    pub metric: Metric,
+    /// Running total across buckets instead of per-bucket values.
+    #[serde(default)]
+    pub cumulative: bool,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcentrationQuery {
+    pub trade_date: String,
+    #[serde(default)]
+    pub fund_id: Option<u32>,
+    #[serde(default)]
+    pub portfolio_manager_id: Option<u32>,
+    /// Percent of gross at which a name is flagged `warn`; defaults to the service config.
+    #[serde(default)]
+    pub warn_pct: Option<f64>,
+    /// Percent of gross at which a name is flagged `breach`; defaults to the service config.
+    #[serde(default)]
+    pub breach_pct: Option<f64>,
+    /// Split ETF/ETC trades at query time instead of reading exploded `Constituent` rows.
+    #[serde(default)]
+    pub look_through: bool,
+    #[serde(default = "default_concentration_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+/// Body of `POST /api/v1/limits` and `PUT /api/v1/limits/{id}`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RiskLimitSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: LimitKind,
+    /// Group the limit applies per, e.g. `desk` or `fund_id`.
+    pub dimension: Dimension,
+    /// Only this group when set; every group otherwise.
+    #[serde(default)]
+    pub scope_value: Option<String>,
+    /// Metric cap, or the percent of gross for concentration limits.
+    pub max_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LimitKind {
+    /// Cap on a pivot metric aggregated per group, e.g. gross notional per desk.
+    Metric {
+        metric: Metric,
+        /// Compare `|value|`, e.g. for delta limits that apply both ways.
+        #[serde(default)]
+        absolute: bool,
+    },
+    /// Cap on the largest single-name share of the group's gross exposure.
+    Concentration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitBreachesQuery {
+    pub trade_date: String,
+    /// Also return groups within their limits.
+    #[serde(default)]
+    pub include_ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StressRequest {
+    /// Dimensions the stressed P&L is aggregated by; empty for a single total.
+    #[serde(default)]
+    pub dimensions: Vec<Dimension>,
+    #[serde(default)]
+    pub filters: PivotFilters,
+    /// Name of a stored shock set, e.g. `Stress`.
+    #[serde(default)]
+    pub shock_set: Option<String>,
+    /// Ad hoc shocks, used instead of a stored set.
+    #[serde(default)]
+    pub shocks: Vec<Shock>,
+    /// Stress each row with the stored set named by its `scenario` column.
+    #[serde(default)]
+    pub by_row_scenario: bool,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+/// Market move applied to the rows it matches. When several shocks match a row the
+/// most specific wins: an `underlying_symbol` shock, then an `asset_class` one, then
+/// an unscoped one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Shock {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlying_symbol: Option<String>,
+    /// Relative spot move in percent, e.g. `-10`.
+    #[serde(default)]
+    pub spot_pct: f64,
+    /// Implied volatility move in vol points, e.g. `5`.
+    #[serde(default)]
+    pub vol_points: f64,
+    /// Interest rate move in basis points, e.g. `25`.
+    #[serde(default)]
+    pub rate_bp: f64,
+    /// Days of time decay.
+    #[serde(default)]
+    pub days: f64,
+}
+
+/// Body of `PUT /api/v1/stress/shock-sets/{name}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShockSetSpec {
+    #[serde(default)]
+    pub description: String,
+    pub shocks: Vec<Shock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarQuery {
+    /// Date of the trades the historical returns are applied to; earlier days'
+    /// positions are not carried into it.
+    pub trade_date: String,
+    #[serde(default = "default_var_group_by")]
+    pub group_by: Dimension,
+    /// Confidence level, e.g. `0.99` for 99% VaR.
+    #[serde(default = "default_var_confidence")]
+    pub confidence: f64,
+    /// Number of daily returns in the simulation, ending on `trade_date`.
+    #[serde(default = "default_var_lookback")]
+    pub lookback_days: u32,
+    #[serde(default)]
+    pub fund_id: Option<u32>,
+    #[serde(default)]
+    pub portfolio_manager_id: Option<u32>,
+    #[serde(default)]
+    pub desk: Option<String>,
+    /// Currency exposures are converted into before netting; `USD` when omitted.
+    #[serde(default)]
+    pub reporting_currency: Option<String>,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionsQuery {
+    /// Positions include every trade up to and including this date.
+    pub as_of: String,
+    #[serde(default)]
+    pub level: PositionLevel,
+    /// At least one of `fund_id`, `portfolio_manager_id` and `symbol` is required.
+    #[serde(default)]
+    pub fund_id: Option<u32>,
+    #[serde(default)]
+    pub portfolio_manager_id: Option<u32>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// Include positions that have been closed out.
+    #[serde(default)]
+    pub include_flat: bool,
+    #[serde(default = "default_positions_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub cache_bypass: bool,
+}
+
+/// Holder positions are netted at.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionLevel {
+    #[default]
+    Account,
+    Portfolio,
+}
+
+impl PositionLevel {
+    pub fn to_column(&self) -> &'static str {
+        match self {
+            PositionLevel::Account => "account_id",
+            PositionLevel::Portfolio => "portfolio_id",
+        }
+    }
+}
+
+fn default_positions_limit() -> u32 {
+    1000
+}
+
+fn default_var_group_by() -> Dimension {
+    Dimension::FundId
+}
+
+fn default_var_confidence() -> f64 {
+    0.99
+}
+
+fn default_var_lookback() -> u32 {
+    250
+}
+
+fn default_concentration_limit() -> u32 {
+    50
+}
+
+fn default_timeseries_metric() -> Metric {
+    Metric::Pnl
+}
+
+/// Bucket width of a time series.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInterval {
+    #[default]
+    FiveMinute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+}
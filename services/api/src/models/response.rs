@@ -1,16 +1,74 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::models::request::{RiskLimitSpec, Shock};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PivotResponse {
     pub data: Vec<PivotRow>,
+    /// Column axis of a cross-tab; absent for flat pivots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<ColumnAxis>,
     pub metadata: QueryMetadata,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnAxis {
+    pub dimensions: Vec<String>,
+    /// Distinct column values, one entry per header, in the same order as each row's `cells`.
+    pub headers: Vec<Vec<String>>,
+    /// `true` when more distinct values existed than `max_columns`.
+    pub truncated: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PivotRow {
     pub dimensions: HashMap<String, serde_json::Value>,
-    pub metrics: HashMap<String, f64>,
+    pub metrics: HashMap<String, MetricValue>,
+    /// Cross-tab metric cells, aligned with `ColumnAxis::headers`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cells: Vec<HashMap<String, MetricValue>>,
+    /// Dimensions aggregated away on subtotal/total rows; empty for detail rows.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aggregated: Vec<String>,
+    /// Current vs prior period per metric alias, when the request has `compare`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub comparison: HashMap<String, MetricComparison>,
+}
+
+/// A metric value: counts are integers, everything else a float.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Integer(i64),
+    Float(f64),
+}
+
+impl MetricValue {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            MetricValue::Integer(n) => *n as f64,
+            MetricValue::Float(x) => *x,
+        }
+    }
+}
+
+impl std::fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricValue::Integer(n) => write!(f, "{}", n),
+            MetricValue::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricComparison {
+    pub current: Option<f64>,
+    pub prior: Option<f64>,
+    pub change: Option<f64>,
+    /// Change relative to the magnitude of `prior`, in percent; `None` when `prior` is zero.
+    pub change_pct: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +77,12 @@ pub struct QueryMetadata {
     pub returned_rows: usize,
     pub query_time_ms: u64,
     pub cached: bool,
+    /// Table that served the query, e.g. `pivot.trades_1d_rollup`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_table: Option<String>,
+    /// Cursor for the next page when this one was full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,6 +116,27 @@ pub struct Constituent {
     pub weight: f64,
     pub shares_per_unit: f64,
     pub effective_date: String,
+    /// First date the version is no longer in effect.
+    pub expiry_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConstituentDiffResponse {
+    pub parent_symbol: String,
+    pub from_date: String,
+    pub to_date: String,
+    pub added: Vec<Constituent>,
+    pub removed: Vec<Constituent>,
+    pub reweighted: Vec<ConstituentChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstituentChange {
+    pub constituent_symbol: String,
+    pub from_weight: f64,
+    pub to_weight: f64,
+    pub change: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -80,4 +165,249 @@ pub struct PnlRow {
     pub total_pnl: f64,
     pub total_notional: f64,
     pub trade_count: u64,
+    /// Requested window values by alias, e.g. `total_pnl_mtd`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub windows: HashMap<String, f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PnlAttributionResponse {
+    pub trade_date: String,
+    pub data: Vec<PnlAttributionRow>,
+    pub metadata: QueryMetadata,
+}
+
+/// Reported P&L of one group split into buckets that sum back to `total_pnl`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PnlAttributionRow {
+    pub groups: HashMap<String, serde_json::Value>,
+    pub total_pnl: f64,
+    pub delta_pnl: f64,
+    pub gamma_pnl: f64,
+    pub vega_pnl: f64,
+    pub theta_pnl: f64,
+    /// Fees paid, as a negative amount.
+    pub fees_drag: f64,
+    /// Slippage cost, as a negative amount.
+    pub slippage_drag: f64,
+    /// Whatever the Greeks and costs do not explain.
+    pub unexplained_pnl: f64,
+    pub trade_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeseriesResponse {
+    pub data: Vec<TimeseriesSeries>,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeseriesSeries {
+    pub groups: HashMap<String, serde_json::Value>,
+    pub points: Vec<TimeseriesPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeseriesPoint {
+    /// Bucket start, e.g. `2024-01-15 09:35:00` or `2024-01-01`.
+    pub bucket: String,
+    /// `None` where a ratio metric is undefined; gap-filled buckets of sums are `0`.
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConcentrationResponse {
+    pub data: Vec<ConcentrationRow>,
+    /// Sum of the absolute net exposure of every name in scope.
+    pub gross_exposure: f64,
+    pub warn_pct: f64,
+    pub breach_pct: f64,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConcentrationRow {
+    pub underlying_symbol: String,
+    pub direct_exposure: f64,
+    /// Exposure held through ETF/ETC constituents.
+    pub etf_exposure: f64,
+    /// With look-through, the part of an ETF/ETC its basket doesn't explain; the row's
+    /// `underlying_symbol` is then the composite itself.
+    pub residual_exposure: f64,
+    pub total_exposure: f64,
+    pub pct_of_gross: f64,
+    pub status: ConcentrationStatus,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConcentrationStatus {
+    Ok,
+    Warn,
+    Breach,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskLimit {
+    pub limit_id: String,
+    #[serde(flatten)]
+    pub spec: RiskLimitSpec,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LimitsResponse {
+    pub limits: Vec<RiskLimit>,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LimitBreachesResponse {
+    pub trade_date: String,
+    /// Most utilised first.
+    pub data: Vec<LimitUtilisation>,
+    pub limits_evaluated: usize,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitUtilisation {
+    pub limit_id: String,
+    pub name: String,
+    pub dimension: String,
+    pub group: serde_json::Value,
+    /// Largest name in the group, for concentration limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlying_symbol: Option<String>,
+    pub value: f64,
+    pub max_value: f64,
+    pub utilisation_pct: f64,
+    pub breached: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StressResponse {
+    pub data: Vec<StressRow>,
+    pub metadata: QueryMetadata,
+}
+
+/// Taylor approximation of the P&L under the shocks, split by Greek.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StressRow {
+    pub dimensions: HashMap<String, serde_json::Value>,
+    /// Reported P&L before the shock.
+    pub base_pnl: f64,
+    pub delta_pnl: f64,
+    pub gamma_pnl: f64,
+    pub vega_pnl: f64,
+    pub rho_pnl: f64,
+    pub theta_pnl: f64,
+    /// Sum of the Greek contributions.
+    pub stress_pnl: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShockSet {
+    pub name: String,
+    pub description: String,
+    pub shocks: Vec<Shock>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShockSetsResponse {
+    pub shock_sets: Vec<ShockSet>,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VarResponse {
+    pub trade_date: String,
+    /// Column the groups are values of, e.g. `fund_id`.
+    pub dimension: String,
+    pub confidence: f64,
+    /// Currency every VaR figure is in.
+    pub currency: String,
+    /// Number of historical scenarios simulated.
+    pub scenarios: usize,
+    /// 1-day VaR of everything in scope, as a positive loss.
+    pub var: f64,
+    /// Expected shortfall: mean loss over the scenarios at or beyond `var`.
+    pub expected_shortfall: f64,
+    pub data: Vec<VarGroupRow>,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VarGroupRow {
+    pub group: serde_json::Value,
+    /// Net exposure of the group, in the response currency.
+    pub exposure: f64,
+    /// VaR of the group on its own.
+    pub var: f64,
+    pub expected_shortfall: f64,
+    /// Change in portfolio VaR per unit of the group's exposure; `None` when the
+    /// group nets to zero.
+    pub marginal_var: Option<f64>,
+    /// Portfolio VaR minus the portfolio VaR without this group.
+    pub incremental_var: f64,
+    /// Loss of the group in the portfolio VaR scenario; sums to the portfolio VaR.
+    pub component_var: f64,
+    /// Mean loss of the group over the portfolio tail; sums to the portfolio ES.
+    pub component_es: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PositionsResponse {
+    pub as_of: String,
+    /// Column the positions are netted by, `account_id` or `portfolio_id`.
+    pub dimension: String,
+    /// Totals over every position in scope, before `limit` applies, one per currency.
+    pub summary: Vec<PositionSummary>,
+    pub data: Vec<Position>,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PositionSummary {
+    /// Currency every amount in this summary is in.
+    pub currency: String,
+    pub long_positions: usize,
+    pub short_positions: usize,
+    pub long_market_value: f64,
+    /// Market value of short positions, as a negative amount.
+    pub short_market_value: f64,
+    pub gross_market_value: f64,
+    pub net_market_value: f64,
+    pub realised_pnl: f64,
+    pub unrealised_pnl: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Position {
+    pub group: serde_json::Value,
+    pub symbol: String,
+    pub asset_class: String,
+    pub currency: String,
+    pub side: PositionSide,
+    /// Net quantity carried into the end of `as_of`; negative when short.
+    pub quantity: f64,
+    /// Net quantity traded on `as_of` itself.
+    pub day_quantity: f64,
+    /// Average cost of the open quantity.
+    pub avg_cost: f64,
+    /// Last traded price on or before `as_of`.
+    pub last_price: f64,
+    pub market_value: f64,
+    /// Gain on quantity closed out against the average cost.
+    pub realised_pnl: f64,
+    /// Gain on the open quantity at `last_price`.
+    pub unrealised_pnl: f64,
+    pub last_trade_date: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionSide {
+    Long,
+    Short,
+    Flat,
 }
@@ -0,0 +1,106 @@
+//! P&L explain: reported P&L split into Greek, cost and unexplained buckets.
+//!
+//! Each trade's Greeks are applied to its underlying's move since the previous
+//! price date, scaled by `quantity` as in [`crate::query::stress`]:
+//!
+//! - delta: `delta·ΔS`, gamma: `½·gamma·ΔS²`, with `ΔS = price · return`
+//! - vega: `vega·Δσ`, `Δσ` the change in the underlying's average implied `vol`
+//!   since the previous trade date, in vol points
+//! - theta: `theta·Δt`, `Δt` the calendar days since the previous price date
+//!
+//! Reported `pnl` is taken as net of costs, so `fees` and slippage (a fraction of
+//! notional) are shown as drags and whatever remains is unexplained.
+
+use crate::query::params::{BoundQuery, QueryParams};
+
+/// How far back the previous close is looked for, covering weekends and holidays.
+const MAX_GAP_DAYS: u32 = 10;
+
+/// Buckets returned for each group, in order.
+pub const ATTRIBUTION_COLUMNS: &[&str] = &[
+    "total_pnl",
+    "delta_pnl",
+    "gamma_pnl",
+    "vega_pnl",
+    "theta_pnl",
+    "fees_drag",
+    "slippage_drag",
+    "unexplained_pnl",
+];
+
+/// Attribution of `trade_date` P&L per combination of `group_by` columns. The
+/// columns must already be validated against the P&L whitelist.
+pub fn attribution_query(group_by: &[&str], trade_date: &str) -> BoundQuery {
+    let mut params = QueryParams::new();
+    let date = params.bind("Date", trade_date);
+
+    let select_groups: Vec<String> = group_by.iter().map(|c| format!("t.{} AS {}", c, c)).collect();
+    let sql = format!(
+        "SELECT {groups}, sum(t.pnl) AS total_pnl, \
+         sum(t.quantity * t.delta * t.price * m.d_ret) AS delta_pnl, \
+         sum(0.5 * t.quantity * t.gamma * pow(t.price * m.d_ret, 2)) AS gamma_pnl, \
+         sum(t.quantity * t.vega * v.d_vol) AS vega_pnl, \
+         sum(t.quantity * t.theta * m.d_days) AS theta_pnl, \
+         -sum(t.fees) AS fees_drag, \
+         -sum(abs(t.notional) * t.slippage) AS slippage_drag, \
+         total_pnl - delta_pnl - gamma_pnl - vega_pnl - theta_pnl - fees_drag - slippage_drag AS unexplained_pnl, \
+         count() AS trade_count \
+         FROM pivot.trades_1d AS t \
+         LEFT JOIN (\
+         SELECT symbol AS move_symbol, \
+         countIf(price_date = {date}) > 0 AND countIf(price_date < {date}) > 0 AS has_move, \
+         if(has_move, anyIf(close, price_date = {date}) / argMaxIf(close, price_date, price_date < {date}) - 1, 0) AS d_ret, \
+         if(has_move, dateDiff('day', maxIf(price_date, price_date < {date}), {date}), 0) AS d_days \
+         FROM pivot.price_history FINAL \
+         WHERE price_date BETWEEN {date} - {gap} AND {date} \
+         GROUP BY symbol\
+         ) AS m ON m.move_symbol = t.underlying_symbol \
+         LEFT JOIN (\
+         SELECT underlying_symbol AS vol_symbol, \
+         ifNotFinite(avgIf(vol, trade_date = {date}) - avgIf(vol, trade_date < {date}), 0) * 100 AS d_vol \
+         FROM pivot.trades_1d \
+         WHERE trade_date = {date} \
+         OR trade_date = (SELECT max(trade_date) FROM pivot.trades_1d WHERE trade_date < {date}) \
+         GROUP BY underlying_symbol\
+         ) AS v ON v.vol_symbol = t.underlying_symbol \
+         WHERE t.trade_date = {date} \
+         GROUP BY {group_cols} \
+         ORDER BY total_pnl DESC \
+         LIMIT 100",
+        groups = select_groups.join(", "),
+        date = date,
+        gap = MAX_GAP_DAYS,
+        group_cols = group_by.join(", "),
+    );
+
+    BoundQuery::new(sql, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribution_query() {
+        let bound = attribution_query(&["desk", "book"], "2024-01-15");
+
+        assert!(bound.sql.starts_with(
+            "SELECT t.desk AS desk, t.book AS book, sum(t.pnl) AS total_pnl, \
+             sum(t.quantity * t.delta * t.price * m.d_ret) AS delta_pnl"
+        ));
+        assert!(bound.sql.contains("ON m.move_symbol = t.underlying_symbol"));
+        assert!(bound.sql.contains("WHERE price_date BETWEEN {p0:Date} - 10 AND {p0:Date}"));
+        assert!(bound.sql.contains("WHERE t.trade_date = {p0:Date} GROUP BY desk, book"));
+        assert_eq!(bound.params.get("p0"), Some("2024-01-15"));
+    }
+
+    #[test]
+    fn test_unexplained_is_residual_of_every_bucket() {
+        let bound = attribution_query(&["desk"], "2024-01-15");
+
+        let residual = ATTRIBUTION_COLUMNS[1..ATTRIBUTION_COLUMNS.len() - 1].join(" - ");
+        assert!(bound
+            .sql
+            .contains(&format!("total_pnl - {} AS unexplained_pnl", residual)));
+    }
+}
@@ -1,15 +1,103 @@
 use crate::error::ApiError;
-use crate::models::request::{ExposureType, PivotFilters, PivotRequest, SortDirection};
-use crate::query::{Dimension, Metric};
+use crate::models::request::{
+    MetricFilter, NullsOrder, PivotFilters, PivotRequest, Predicate, SortDirection, SortSpec, TopN,
+    TotalsMode, OTHER_BUCKET,
+};
+use crate::models::response::PivotRow;
+use crate::query::cursor::{decode_cursor, encode_cursor, keyset_predicate, KeyColumn};
+use crate::query::expr::{validate_name, Expr, MEASURE_COLUMNS};
+use crate::query::fx::{converted_source, validate_currency};
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+use crate::query::planner::SourceTable;
+use crate::query::predicate::compile_predicate;
+use crate::query::window::lookback_start;
+use crate::query::{Dimension, Metric, WindowKind, WindowMetric};
+
+/// `trades_1d` columns that are neither a [`Dimension`] nor in [`MEASURE_COLUMNS`].
+const OTHER_TRADE_COLUMNS: &[&str] = &["ts", "trade_id", "order_id"];
+
+/// Current and prior period predicates of a comparison.
+type Periods = (Vec<Predicate>, Vec<Predicate>);
 
 pub struct PivotQueryBuilder {
     dimensions: Vec<Dimension>,
+    columns: Vec<Dimension>,
+    column_headers: Vec<Vec<String>>,
+    max_columns: u32,
     metrics: Vec<Metric>,
-    filters: PivotFilters,
-    sort_field: Option<String>,
-    sort_direction: SortDirection,
+    calculated: Vec<(String, Expr)>,
+    windows: Vec<WindowMetric>,
+    /// Requested `(start, end)` trade dates when windows read further back than the filters.
+    window_range: Option<(String, String)>,
+    predicates: Vec<Predicate>,
+    periods: Option<Periods>,
+    top_n: Option<TopN>,
+    reporting_currency: Option<String>,
+    having: Vec<MetricFilter>,
+    sort: Vec<SortSpec>,
     limit: u32,
     offset: u32,
+    cursor: Option<Vec<Option<String>>>,
+    totals: TotalsMode,
+    source: SourceTable,
+}
+
+/// Alias of the `grouping()` bitmask selected when subtotals are requested.
+pub const GROUPING_ID_ALIAS: &str = "grouping_id";
+
+/// Upper bound on distinct column headers in a cross-tab, whatever the request asks for.
+pub const MAX_COLUMN_HEADERS: u32 = 200;
+
+/// Upper bound on `top_n.n`.
+pub const MAX_TOP_N: u32 = 1000;
+
+/// Alias of a cross-tab cell: metric `alias` under column header `index`.
+pub fn cell_alias(index: usize, alias: &str) -> String {
+    format!("c{}__{}", index, alias)
+}
+
+/// Inverse of [`cell_alias`].
+pub fn parse_cell_alias(key: &str) -> Option<(usize, &str)> {
+    let (index, alias) = key.strip_prefix('c')?.split_once("__")?;
+    Some((index.parse().ok()?, alias))
+}
+
+/// Comparison columns selected alongside each metric in period comparison mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonPart {
+    Prior,
+    Change,
+    ChangePct,
+}
+
+impl ComparisonPart {
+    const ALL: [ComparisonPart; 3] = [
+        ComparisonPart::Prior,
+        ComparisonPart::Change,
+        ComparisonPart::ChangePct,
+    ];
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            ComparisonPart::Prior => "prior",
+            ComparisonPart::Change => "change",
+            ComparisonPart::ChangePct => "change_pct",
+        }
+    }
+}
+
+/// Alias of a comparison column of metric `alias`, e.g. `total_pnl__change`.
+pub fn comparison_alias(alias: &str, part: ComparisonPart) -> String {
+    format!("{}__{}", alias, part.suffix())
+}
+
+/// Inverse of [`comparison_alias`].
+pub fn parse_comparison_alias(key: &str) -> Option<(&str, ComparisonPart)> {
+    let (alias, suffix) = key.rsplit_once("__")?;
+    ComparisonPart::ALL
+        .into_iter()
+        .find(|part| part.suffix() == suffix)
+        .map(|part| (alias, part))
 }
 
 impl PivotQueryBuilder {
@@ -20,69 +108,279 @@ impl PivotQueryBuilder {
             ));
         }
 
-        if req.metrics.is_empty() {
+        if req.metrics.is_empty() && req.calculated_metrics.is_empty() {
             return Err(ApiError::QueryValidation(
                 "At least one metric is required".to_string(),
             ));
         }
 
-        let (sort_field, sort_direction) = match &req.sort {
-            Some(sort) => (Some(sort.field.clone()), sort.direction.clone()),
-            None => (None, SortDirection::Desc),
+        if let Some(dim) = req.columns.iter().find(|d| req.dimensions.contains(d)) {
+            return Err(ApiError::QueryValidation(format!(
+                "Dimension '{}' cannot be on both rows and columns",
+                dim.to_column()
+            )));
+        }
+
+        if req.max_columns == 0 || req.max_columns > MAX_COLUMN_HEADERS {
+            return Err(ApiError::QueryValidation(format!(
+                "max_columns must be between 1 and {}",
+                MAX_COLUMN_HEADERS
+            )));
+        }
+
+        if let Some(ref date) = req.filters.trade_date {
+            validate_date("trade_date", date)?;
+        }
+        if let Some(ref range) = req.filters.trade_date_range {
+            validate_date("trade_date_range.start", &range.start)?;
+            validate_date("trade_date_range.end", &range.end)?;
+        }
+
+        let (mut predicates, periods) = Self::split_periods(req)?;
+        Self::validate_windows(req)?;
+        Self::validate_top_n(req)?;
+        let window_range = Self::window_range(req);
+        if window_range.is_some() {
+            predicates = PivotFilters {
+                trade_date: None,
+                trade_date_range: None,
+                ..req.filters.clone()
+            }
+            .to_predicates();
+        }
+        let period_predicates = periods.iter().flat_map(|(cur, prior)| cur.iter().chain(prior));
+        for predicate in predicates.iter().chain(period_predicates) {
+            compile_predicate(predicate, &mut QueryParams::new())?;
+        }
+
+        let calculated = Self::compile_calculated_metrics(req)?;
+        Self::validate_sort(req)?;
+        Self::validate_having(req)?;
+
+        let cursor = match req.cursor {
+            Some(ref token) => {
+                if req.offset > 0 {
+                    return Err(ApiError::QueryValidation(
+                        "cursor and offset cannot be combined".to_string(),
+                    ));
+                }
+                if req.totals != TotalsMode::None {
+                    return Err(ApiError::QueryValidation(
+                        "cursor pagination is not supported with totals".to_string(),
+                    ));
+                }
+                Some(decode_cursor(token)?)
+            }
+            None => None,
+        };
+
+        let all_dimensions: Vec<Dimension> =
+            req.dimensions.iter().chain(&req.columns).copied().collect();
+        let all_metrics: Vec<Metric> = req
+            .metrics
+            .iter()
+            .copied()
+            .chain(req.window_metrics.iter().map(|w| w.metric))
+            .chain(req.top_n.iter().map(|t| t.by))
+            .collect();
+        if let Some(ref currency) = req.reporting_currency {
+            validate_currency(currency)?;
+        }
+        // The rollup holds pre-summed amounts, so it can't be converted per trade date
+        let source = if req.look_through {
+            SourceTable::LookThrough
+        } else if calculated.is_empty() && req.reporting_currency.is_none() {
+            SourceTable::plan(&all_dimensions, &all_metrics, &req.filters)
+        } else {
+            SourceTable::Trades
         };
 
-        Ok(Self {
+        let builder = Self {
             dimensions: req.dimensions.clone(),
+            columns: req.columns.clone(),
+            column_headers: Vec::new(),
+            max_columns: req.max_columns,
             metrics: req.metrics.clone(),
-            filters: req.filters.clone(),
-            sort_field,
-            sort_direction,
+            calculated,
+            windows: req.window_metrics.clone(),
+            window_range,
+            predicates,
+            periods,
+            top_n: req.top_n.clone(),
+            reporting_currency: req.reporting_currency.clone(),
+            having: req.having.clone(),
+            sort: req.sort.clone(),
             limit: req.limit,
             offset: req.offset,
-        })
+            cursor,
+            totals: req.totals,
+            source,
+        };
+
+        if let Some(ref values) = builder.cursor {
+            keyset_predicate(&builder.key_columns(), values, &mut QueryParams::new())?;
+        }
+
+        Ok(builder)
+    }
+
+    /// Names of the dimensions aggregated away in a row with the given `grouping_id`.
+    ///
+    /// `grouping(d1, ..., dn)` sets bit `n - 1 - i` when dimension `i` is not
+    /// part of the row's grouping set.
+    pub fn aggregated_dimensions(&self, grouping_id: u64) -> Vec<String> {
+        let n = self.dimensions.len();
+        self.dimensions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| grouping_id & (1 << (n - 1 - i)) != 0)
+            .map(|(_, d)| d.to_column().to_string())
+            .collect()
+    }
+
+    /// Table chosen by the planner for this query.
+    pub fn source(&self) -> SourceTable {
+        self.source
+    }
+
+    /// Whether `key` is the alias of one of the requested metrics.
+    pub fn is_metric_alias(&self, key: &str) -> bool {
+        self.metrics.iter().any(|m| m.alias() == key)
+            || self.calculated.iter().any(|(name, _)| name == key)
+            || self.windows.iter().any(|w| w.alias() == key)
+    }
+
+    /// Whether the metric `alias` is a whole-number count, e.g. `trade_count` or a rank.
+    pub fn is_integer_metric(&self, alias: &str) -> bool {
+        self.metrics.iter().any(|m| m.alias() == alias && m.is_integer())
+            || self.windows.iter().any(|w| {
+                w.alias() == alias && (w.kind == WindowKind::Rank || w.metric.is_integer())
+            })
+    }
+
+    /// Whether the query compares a current and a prior period.
+    pub fn is_comparison(&self) -> bool {
+        self.periods.is_some()
+    }
+
+    pub fn column_dimensions(&self) -> &[Dimension] {
+        &self.columns
+    }
+
+    pub fn column_headers(&self) -> &[Vec<String>] {
+        &self.column_headers
+    }
+
+    /// Query for the distinct column headers of a cross-tab, or `None` for a flat pivot.
+    ///
+    /// Fetches one header more than the cap so the caller can tell whether it truncated.
+    pub fn build_header_query(&self) -> Option<BoundQuery> {
+        if self.columns.is_empty() {
+            return None;
+        }
+
+        let mut params = QueryParams::new();
+        let cols: Vec<&str> = self.columns.iter().map(|d| d.to_column()).collect();
+        let select: Vec<String> = self.columns.iter().map(|d| d.to_select()).collect();
+        let mut sql = format!(
+            "SELECT DISTINCT {} FROM {}",
+            select.join(", "),
+            self.source_sql(&mut params)
+        );
+
+        let where_clauses = self.build_where_clauses(&mut params, None);
+        if !where_clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&where_clauses.join(" AND "));
+        }
+
+        sql.push_str(&format!(
+            " ORDER BY {} LIMIT {}",
+            cols.join(", "),
+            self.max_columns + 1
+        ));
+
+        Some(BoundQuery::new(sql, params))
+    }
+
+    /// Sets the headers returned by [`Self::build_header_query`], capping them at
+    /// `max_columns`. Returns `true` if some headers were dropped.
+    pub fn set_column_headers(&mut self, mut headers: Vec<Vec<String>>) -> bool {
+        let truncated = headers.len() > self.max_columns as usize;
+        headers.truncate(self.max_columns as usize);
+        self.column_headers = headers;
+        truncated
     }
 
-    pub fn build(&self) -> String {
+    pub fn build(&self) -> BoundQuery {
         let mut sql = String::new();
+        let mut params = QueryParams::new();
 
         // SELECT clause
         sql.push_str("SELECT ");
 
         // Add dimensions
         let dim_cols: Vec<&str> = self.dimensions.iter().map(|d| d.to_column()).collect();
-        sql.push_str(&dim_cols.join(", "));
+        let dim_select: Vec<String> = self.dimensions.iter().map(|d| d.to_select()).collect();
+        sql.push_str(&dim_select.join(", "));
 
         // Add metrics
-        for metric in &self.metrics {
-            sql.push_str(", ");
-            sql.push_str(metric.to_aggregation());
-            sql.push_str(" AS ");
-            sql.push_str(metric.alias());
-        }
+        let periods = self.bind_periods(&mut params);
+        sql.push_str(&self.build_metric_columns(periods.as_ref()));
+        sql.push_str(&self.build_window_columns(&mut params));
 
-        // FROM clause
-        sql.push_str(" FROM pivot.trades_1d");
+        // Cross-tab cells: one conditional aggregate per column header and metric
+        for (index, header) in self.column_headers.iter().enumerate() {
+            let condition = self
+                .columns
+                .iter()
+                .zip(header)
+                .map(|(dim, value)| {
+                    format!("toString({}) = {}", dim.to_expr(), params.bind("String", value))
+                })
+                .collect::<Vec<_>>()
+                .join(" AND ");
 
-        // WHERE clause
-        let where_clauses = self.build_where_clauses();
-        if !where_clauses.is_empty() {
-            sql.push_str(" WHERE ");
-            sql.push_str(&where_clauses.join(" AND "));
+            for metric in &self.metrics {
+                let agg = self
+                    .source
+                    .aggregation_if(metric, &condition)
+                    .unwrap_or_else(|| metric.to_aggregation_if(&condition));
+                sql.push_str(&format!(", {} AS {}", agg, cell_alias(index, metric.alias())));
+            }
+            for (name, expr) in &self.calculated {
+                sql.push_str(&format!(
+                    ", {} AS {}",
+                    expr.to_sql(Some(&condition)),
+                    cell_alias(index, name)
+                ));
+            }
         }
 
-        // GROUP BY clause
-        sql.push_str(" GROUP BY ");
-        sql.push_str(&dim_cols.join(", "));
+        if self.totals != TotalsMode::None {
+            sql.push_str(&format!(
+                ", grouping({}) AS {}",
+                dim_cols.join(", "),
+                GROUPING_ID_ALIAS
+            ));
+        }
 
-        // ORDER BY clause
-        if let Some(ref field) = self.sort_field {
-            sql.push_str(" ORDER BY ");
-            sql.push_str(field);
-            sql.push_str(match self.sort_direction {
-                SortDirection::Asc => " ASC",
-                SortDirection::Desc => " DESC",
-            });
+        // FROM, WHERE and GROUP BY clauses
+        sql.push_str(&self.build_grouped_source(&mut params, periods.as_ref()));
+
+        // HAVING clause: metric filters, then resume after the cursor row
+        let mut having = self.build_having_clauses(&mut params);
+        if let Some(ref values) = self.cursor {
+            let predicate = keyset_predicate(&self.key_columns(), values, &mut params)
+                .expect("cursor is validated up front");
+            having.push(format!("({})", predicate));
         }
+        sql = self.apply_post_filters(sql, having, &mut params);
+
+        // ORDER BY clause
+        let order: Vec<String> = self.key_columns().iter().map(|k| k.order_term()).collect();
+        sql.push_str(" ORDER BY ");
+        sql.push_str(&order.join(", "));
 
         // LIMIT and OFFSET
         sql.push_str(&format!(" LIMIT {}", self.limit));
@@ -90,145 +388,692 @@ impl PivotQueryBuilder {
             sql.push_str(&format!(" OFFSET {}", self.offset));
         }
 
-        sql
+        BoundQuery::new(sql, params)
     }
 
-    fn build_where_clauses(&self) -> Vec<String> {
-        let mut clauses = Vec::new();
+    /// Counts the groups the pivot produces before `LIMIT`/`OFFSET`, as `total_rows`.
+    pub fn build_count_query(&self) -> BoundQuery {
+        let mut params = QueryParams::new();
+        let dim_select: Vec<String> = self.dimensions.iter().map(|d| d.to_select()).collect();
+        let periods = self.bind_periods(&mut params);
+        let metric_columns = if self.having.is_empty() && self.windows.is_empty() {
+            String::new()
+        } else {
+            self.build_metric_columns(periods.as_ref()) + &self.build_window_columns(&mut params)
+        };
+        let inner = format!(
+            "SELECT {}{}{}",
+            dim_select.join(", "),
+            metric_columns,
+            self.build_grouped_source(&mut params, periods.as_ref())
+        );
 
-        if let Some(ref date) = self.filters.trade_date {
-            clauses.push(format!("trade_date = '{}'", Self::escape_string(date)));
-        }
+        let having = self.build_having_clauses(&mut params);
+        let inner = self.apply_post_filters(inner, having, &mut params);
 
-        if let Some(ref range) = self.filters.trade_date_range {
-            clauses.push(format!(
-                "trade_date >= '{}' AND trade_date <= '{}'",
-                Self::escape_string(&range.start),
-                Self::escape_string(&range.end)
-            ));
+        BoundQuery::new(format!("SELECT count() AS total_rows FROM ({})", inner), params)
+    }
+
+    /// Cursor pointing just past `row`, for fetching the next page.
+    ///
+    /// `None` when keyset pagination does not apply, for subtotal rows. A sort value
+    /// missing from the row is NULL and is carried in the cursor as such.
+    pub fn next_cursor(&self, row: &PivotRow) -> Option<String> {
+        if self.totals != TotalsMode::None {
+            return None;
         }
 
-        if let Some(ref types) = self.filters.exposure_type {
-            if !types.is_empty() {
-                let values: Vec<String> = types
+        let values: Vec<Option<String>> = self
+            .key_columns()
+            .iter()
+            .map(|key| match row.dimensions.get(&key.expr) {
+                Some(serde_json::Value::Null) => None,
+                Some(serde_json::Value::String(s)) => Some(s.clone()),
+                Some(other) => Some(other.to_string()),
+                None => row.metrics.get(&key.expr).map(|m| m.to_string()).or_else(|| {
+                    let (alias, part) = parse_comparison_alias(&key.expr)?;
+                    let values = row.comparison.get(alias)?;
+                    let value = match part {
+                        ComparisonPart::Prior => values.prior,
+                        ComparisonPart::Change => values.change,
+                        ComparisonPart::ChangePct => values.change_pct,
+                    };
+                    value.map(|v| v.to_string())
+                }),
+            })
+            .collect();
+
+        Some(encode_cursor(&values))
+    }
+
+    /// Deterministic ordering: the requested sort keys, then every row dimension as a tiebreaker.
+    fn key_columns(&self) -> Vec<KeyColumn> {
+        let mut keys: Vec<KeyColumn> = self
+            .sort
+            .iter()
+            .map(|spec| {
+                let ty = self
+                    .dimensions
                     .iter()
-                    .map(|t| format!("'{}'", Self::exposure_type_to_string(t)))
-                    .collect();
-                clauses.push(format!("exposure_type IN ({})", values.join(", ")));
+                    .find(|d| d.to_column() == spec.field)
+                    .map_or("Float64", |d| d.clickhouse_type());
+                KeyColumn {
+                    expr: spec.field.clone(),
+                    ty,
+                    descending: spec.direction == SortDirection::Desc,
+                    nulls_first: spec.nulls.map(|n| n == NullsOrder::First),
+                    nullable: self.is_nullable_key(&spec.field),
+                }
+            })
+            .collect();
+
+        for dim in &self.dimensions {
+            if !keys.iter().any(|k| k.expr == dim.to_column()) {
+                keys.push(KeyColumn {
+                    expr: dim.to_column().to_string(),
+                    ty: dim.clickhouse_type(),
+                    descending: false,
+                    nulls_first: None,
+                    nullable: false,
+                });
             }
         }
 
-        if let Some(ref ids) = self.filters.portfolio_manager_id {
-            if !ids.is_empty() {
-                let values: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
-                clauses.push(format!("portfolio_manager_id IN ({})", values.join(", ")));
+        keys
+    }
+
+    /// Whether the sort key `key` can be NULL: ratio metrics, calculated metrics (which
+    /// may divide) and the comparison columns derived from them, or any `change_pct`.
+    fn is_nullable_key(&self, key: &str) -> bool {
+        if let Some((alias, part)) = parse_comparison_alias(key) {
+            return part == ComparisonPart::ChangePct || self.is_nullable_key(alias);
+        }
+        self.metrics.iter().any(|m| m.alias() == key && m.is_nullable())
+            || self.calculated.iter().any(|(name, _)| name == key)
+    }
+
+    /// Parses each calculated metric, rejecting names that would collide with other columns.
+    fn compile_calculated_metrics(req: &PivotRequest) -> Result<Vec<(String, Expr)>, ApiError> {
+        let mut compiled: Vec<(String, Expr)> = Vec::new();
+
+        for calc in &req.calculated_metrics {
+            validate_name(&calc.name)?;
+
+            // An alias naming a source column would be substituted into every
+            // aggregate reading that column, e.g. `sum(pnl)`.
+            let clashes = Metric::all().iter().any(|m| m.alias() == calc.name)
+                || Dimension::all().iter().any(|d| d.to_column() == calc.name)
+                || MEASURE_COLUMNS.contains(&calc.name.as_str())
+                || OTHER_TRADE_COLUMNS.contains(&calc.name.as_str())
+                || calc.name == GROUPING_ID_ALIAS
+                || parse_cell_alias(&calc.name).is_some()
+                || parse_comparison_alias(&calc.name).is_some()
+                || req.window_metrics.iter().any(|w| w.alias() == calc.name)
+                || compiled.iter().any(|(name, _)| *name == calc.name);
+            if clashes {
+                return Err(ApiError::QueryValidation(format!(
+                    "Calculated metric name '{}' is already in use",
+                    calc.name
+                )));
             }
+
+            compiled.push((calc.name.clone(), Expr::parse(&calc.expression)?));
         }
 
-        if let Some(ref ids) = self.filters.fund_id {
-            if !ids.is_empty() {
-                let values: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
-                clauses.push(format!("fund_id IN ({})", values.join(", ")));
+        Ok(compiled)
+    }
+
+    /// Metric filters must reference requested metrics and have non-empty groups.
+    fn validate_having(req: &PivotRequest) -> Result<(), ApiError> {
+        fn check(filter: &MetricFilter, valid: &[&str]) -> Result<(), ApiError> {
+            match filter {
+                MetricFilter::And { and: group } | MetricFilter::Or { or: group } => {
+                    if group.is_empty() {
+                        return Err(ApiError::QueryValidation(
+                            "Metric filter groups must not be empty".to_string(),
+                        ));
+                    }
+                    group.iter().try_for_each(|f| check(f, valid))
+                }
+                MetricFilter::Condition(cond) => {
+                    if !valid.contains(&cond.metric.as_str()) {
+                        return Err(ApiError::QueryValidation(format!(
+                            "Invalid metric filter: '{}'. Valid metrics: {:?}",
+                            cond.metric, valid
+                        )));
+                    }
+                    if !cond.value.is_finite() {
+                        return Err(ApiError::QueryValidation(format!(
+                            "Metric filter value for '{}' must be finite",
+                            cond.metric
+                        )));
+                    }
+                    Ok(())
+                }
             }
         }
 
-        if let Some(ref classes) = self.filters.asset_class {
-            if !classes.is_empty() {
-                let values: Vec<String> = classes
-                    .iter()
-                    .map(|c| format!("'{}'", Self::escape_string(c)))
-                    .collect();
-                clauses.push(format!("asset_class IN ({})", values.join(", ")));
+        let valid: Vec<&str> = req
+            .metrics
+            .iter()
+            .map(|m| m.alias())
+            .chain(req.calculated_metrics.iter().map(|c| c.name.as_str()))
+            .collect();
+
+        req.having.iter().try_for_each(|f| check(f, &valid))
+    }
+
+    /// Sort fields must name a requested dimension or metric, each at most once.
+    ///
+    /// In comparison mode a metric's prior value and changes are sortable too.
+    fn validate_sort(req: &PivotRequest) -> Result<(), ApiError> {
+        let metric_aliases: Vec<&str> = req
+            .metrics
+            .iter()
+            .map(|m| m.alias())
+            .chain(req.calculated_metrics.iter().map(|c| c.name.as_str()))
+            .collect();
+
+        let mut valid: Vec<String> = req
+            .dimensions
+            .iter()
+            .map(|d| d.to_column().to_string())
+            .chain(metric_aliases.iter().map(|a| a.to_string()))
+            .chain(req.window_metrics.iter().map(|w| w.alias()))
+            .collect();
+        if req.compare.is_some() {
+            for alias in &metric_aliases {
+                valid.extend(ComparisonPart::ALL.iter().map(|p| comparison_alias(alias, *p)));
             }
         }
 
-        if let Some(ref symbols) = self.filters.symbol {
-            if !symbols.is_empty() {
-                let values: Vec<String> = symbols
-                    .iter()
-                    .map(|s| format!("'{}'", Self::escape_string(s)))
-                    .collect();
-                clauses.push(format!("symbol IN ({})", values.join(", ")));
+        for (i, spec) in req.sort.iter().enumerate() {
+            if !valid.contains(&spec.field) {
+                return Err(ApiError::QueryValidation(format!(
+                    "Invalid sort field: '{}'. Valid fields: {:?}",
+                    spec.field, valid
+                )));
+            }
+            if req.sort[..i].iter().any(|prev| prev.field == spec.field) {
+                return Err(ApiError::QueryValidation(format!(
+                    "Duplicate sort field: '{}'",
+                    spec.field
+                )));
             }
         }
 
-        if let Some(ref symbols) = self.filters.underlying_symbol {
-            if !symbols.is_empty() {
-                let values: Vec<String> = symbols
-                    .iter()
-                    .map(|s| format!("'{}'", Self::escape_string(s)))
-                    .collect();
-                clauses.push(format!("underlying_symbol IN ({})", values.join(", ")));
+        Ok(())
+    }
+
+    /// `, <aggregate> AS <alias>` for every requested and calculated metric.
+    ///
+    /// Given the bound current and prior period conditions, each metric is aggregated
+    /// over the current period and followed by its comparison columns. Conditional
+    /// aggregation keeps groups that only appear in one of the periods.
+    fn build_metric_columns(&self, periods: Option<&(String, String)>) -> String {
+        let mut sql = String::new();
+
+        let mut columns: Vec<(String, Option<String>, &str)> = Vec::new();
+        for metric in &self.metrics {
+            let aggregate = |condition: &str| {
+                self.source
+                    .aggregation_if(metric, condition)
+                    .unwrap_or_else(|| metric.to_aggregation_if(condition))
+            };
+            columns.push(match periods {
+                Some((current, prior)) => {
+                    (aggregate(current), Some(aggregate(prior)), metric.alias())
+                }
+                None => (
+                    self.source
+                        .aggregation(metric)
+                        .unwrap_or_else(|| metric.to_aggregation()),
+                    None,
+                    metric.alias(),
+                ),
+            });
+        }
+        for (name, expr) in &self.calculated {
+            columns.push(match periods {
+                Some((current, prior)) => (
+                    expr.to_sql(Some(current)),
+                    Some(expr.to_sql(Some(prior))),
+                    name.as_str(),
+                ),
+                None => (expr.to_sql(None), None, name.as_str()),
+            });
+        }
+
+        for (current, prior, alias) in columns {
+            sql.push_str(&format!(", {} AS {}", current, alias));
+            if let Some(prior) = prior {
+                let prior_alias = comparison_alias(alias, ComparisonPart::Prior);
+                sql.push_str(&format!(
+                    ", {} AS {prior}, {alias} - {prior} AS {}, \
+                     ({alias} - {prior}) / nullIf(abs({prior}), 0) * 100 AS {}",
+                    prior,
+                    comparison_alias(alias, ComparisonPart::Change),
+                    comparison_alias(alias, ComparisonPart::ChangePct),
+                    prior = prior_alias,
+                    alias = alias,
+                ));
             }
         }
 
-        if let Some(ref desks) = self.filters.desk {
-            if !desks.is_empty() {
-                let values: Vec<String> = desks
-                    .iter()
-                    .map(|d| format!("'{}'", Self::escape_string(d)))
-                    .collect();
-                clauses.push(format!("desk IN ({})", values.join(", ")));
+        sql
+    }
+
+    /// `, <window> AS <alias>` for every window metric.
+    ///
+    /// Period-to-date windows widen the grouped rows back to the period start. Unless
+    /// each trade date already has its own partition, ranks are split on whether the row
+    /// is in the requested range, so the lookback days never take part in the ranking.
+    fn build_window_columns(&self, params: &mut QueryParams) -> String {
+        let non_time: Vec<&str> = self
+            .dimensions
+            .iter()
+            .filter(|d| **d != Dimension::TradeDate)
+            .map(|d| d.to_column())
+            .collect();
+        let mut parents: Vec<String> = self.dimensions[..self.dimensions.len() - 1]
+            .iter()
+            .map(|d| d.to_column().to_string())
+            .collect();
+        let time = Dimension::TradeDate.to_column();
+        if let Some((ref start, _)) = self.window_range {
+            let ranked = self.windows.iter().any(|w| !w.kind.is_cumulative());
+            if ranked && !parents.iter().any(|p| p == time) {
+                parents.push(format!("{} >= {}", time, params.bind("Date", start)));
             }
         }
+        let parents: Vec<&str> = parents.iter().map(String::as_str).collect();
 
-        if let Some(ref books) = self.filters.book {
-            if !books.is_empty() {
-                let values: Vec<String> = books
-                    .iter()
-                    .map(|b| format!("'{}'", Self::escape_string(b)))
-                    .collect();
-                clauses.push(format!("book IN ({})", values.join(", ")));
+        let mut sql = String::new();
+        for window in &self.windows {
+            let aggregate = self
+                .source
+                .aggregation(&window.metric)
+                .unwrap_or_else(|| window.metric.to_aggregation());
+            let partition = if window.kind.is_cumulative() { &non_time } else { &parents };
+            sql.push_str(&format!(
+                ", {} AS {}",
+                window.to_sql(&aggregate, partition, time),
+                window.alias()
+            ));
+        }
+        sql
+    }
+
+    /// Applies metric filters to a grouped query: as `HAVING`, or with window metrics as an
+    /// outer `WHERE`, so that neither they nor the requested date range change window values.
+    fn apply_post_filters(
+        &self,
+        mut sql: String,
+        mut filters: Vec<String>,
+        params: &mut QueryParams,
+    ) -> String {
+        if self.windows.is_empty() {
+            if !filters.is_empty() {
+                sql.push_str(" HAVING ");
+                sql.push_str(&filters.join(" AND "));
             }
+            return sql;
         }
 
-        if let Some(ref regions) = self.filters.region {
-            if !regions.is_empty() {
-                let values: Vec<String> = regions
-                    .iter()
-                    .map(|r| format!("'{}'", Self::escape_string(r)))
-                    .collect();
-                clauses.push(format!("region IN ({})", values.join(", ")));
+        if let Some((ref start, ref end)) = self.window_range {
+            filters.insert(
+                0,
+                format!(
+                    "trade_date >= {} AND trade_date <= {}",
+                    params.bind("Date", start),
+                    params.bind("Date", end)
+                ),
+            );
+        }
+
+        let mut wrapped = format!("SELECT * FROM ({})", sql);
+        if !filters.is_empty() {
+            wrapped.push_str(" WHERE ");
+            wrapped.push_str(&filters.join(" AND "));
+        }
+        wrapped
+    }
+
+    fn validate_windows(req: &PivotRequest) -> Result<(), ApiError> {
+        if req.window_metrics.is_empty() {
+            return Ok(());
+        }
+
+        let conflict = if req.totals != TotalsMode::None {
+            Some("totals")
+        } else if !req.columns.is_empty() {
+            Some("columns")
+        } else if req.compare.is_some() {
+            Some("compare")
+        } else {
+            None
+        };
+        if let Some(option) = conflict {
+            return Err(ApiError::QueryValidation(format!(
+                "window_metrics cannot be combined with {}",
+                option
+            )));
+        }
+
+        for (i, window) in req.window_metrics.iter().enumerate() {
+            if window.kind.is_cumulative() {
+                if !req.dimensions.contains(&Dimension::TradeDate) {
+                    return Err(ApiError::QueryValidation(format!(
+                        "Window metric '{}' needs trade_date as a row dimension",
+                        window.alias()
+                    )));
+                }
+                if !window.metric.is_additive() {
+                    return Err(ApiError::QueryValidation(format!(
+                        "Window metric '{}' needs an additive metric",
+                        window.alias()
+                    )));
+                }
+            }
+            if req.window_metrics[..i].contains(window) {
+                return Err(ApiError::QueryValidation(format!(
+                    "Duplicate window metric: '{}'",
+                    window.alias()
+                )));
             }
         }
 
-        if let Some(ref countries) = self.filters.country {
-            if !countries.is_empty() {
-                let values: Vec<String> = countries
+        Ok(())
+    }
+
+    fn validate_top_n(req: &PivotRequest) -> Result<(), ApiError> {
+        let Some(ref top) = req.top_n else {
+            return Ok(());
+        };
+
+        let column = top.dimension.to_column();
+        if !req.dimensions.contains(&top.dimension) {
+            return Err(ApiError::QueryValidation(format!(
+                "top_n dimension '{}' must be a row dimension",
+                column
+            )));
+        }
+        if top.dimension.is_derived() || top.dimension.clickhouse_type() != "String" {
+            return Err(ApiError::QueryValidation(format!(
+                "top_n is only supported on stored text dimensions, not '{}'",
+                column
+            )));
+        }
+        if top.n == 0 || top.n > MAX_TOP_N {
+            return Err(ApiError::QueryValidation(format!(
+                "top_n.n must be between 1 and {}",
+                MAX_TOP_N
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// The requested trade dates, when period-to-date windows need earlier rows too.
+    fn window_range(req: &PivotRequest) -> Option<(String, String)> {
+        if !req.window_metrics.iter().any(|w| w.kind.period_start().is_some()) {
+            return None;
+        }
+
+        match (&req.filters.trade_date, &req.filters.trade_date_range) {
+            (Some(date), None) => Some((date.clone(), date.clone())),
+            (None, Some(range)) => Some((range.start.clone(), range.end.clone())),
+            _ => None,
+        }
+    }
+
+    /// Compiles the comparison periods into `(current, prior)` conditions.
+    fn bind_periods(&self, params: &mut QueryParams) -> Option<(String, String)> {
+        let (current, prior) = self.periods.as_ref()?;
+        let mut condition = |predicates: &[Predicate]| {
+            predicates
+                .iter()
+                .map(|p| compile_predicate(p, params).expect("predicates are validated up front"))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        };
+        let current = condition(current);
+        let prior = condition(prior);
+        Some((current, prior))
+    }
+
+    /// Splits the filters into plain predicates and, in comparison mode, the
+    /// current and prior `trade_date` periods.
+    fn split_periods(
+        req: &PivotRequest,
+    ) -> Result<(Vec<Predicate>, Option<Periods>), ApiError> {
+        let Some(ref compare) = req.compare else {
+            return Ok((req.filters.to_predicates(), None));
+        };
+
+        if !req.columns.is_empty() {
+            return Err(ApiError::QueryValidation(
+                "compare cannot be combined with columns".to_string(),
+            ));
+        }
+
+        let current = PivotFilters {
+            trade_date: req.filters.trade_date.clone(),
+            trade_date_range: req.filters.trade_date_range.clone(),
+            ..Default::default()
+        }
+        .to_predicates();
+        let prior = compare.to_predicates();
+        if current.is_empty() || prior.is_empty() {
+            return Err(ApiError::QueryValidation(
+                "compare needs a trade_date or trade_date_range in both filters and compare"
+                    .to_string(),
+            ));
+        }
+
+        let rest = PivotFilters {
+            trade_date: None,
+            trade_date_range: None,
+            ..req.filters.clone()
+        };
+        Ok((rest.to_predicates(), Some((current, prior))))
+    }
+
+    fn build_having_clauses(&self, params: &mut QueryParams) -> Vec<String> {
+        self.having
+            .iter()
+            .map(|filter| Self::compile_metric_filter(filter, params))
+            .collect()
+    }
+
+    fn compile_metric_filter(filter: &MetricFilter, params: &mut QueryParams) -> String {
+        match filter {
+            MetricFilter::And { and } => {
+                let parts: Vec<String> = and
                     .iter()
-                    .map(|c| format!("'{}'", Self::escape_string(c)))
+                    .map(|f| Self::compile_metric_filter(f, params))
                     .collect();
-                clauses.push(format!("country IN ({})", values.join(", ")));
+                format!("({})", parts.join(" AND "))
+            }
+            MetricFilter::Or { or } => {
+                let parts: Vec<String> = or
+                    .iter()
+                    .map(|f| Self::compile_metric_filter(f, params))
+                    .collect();
+                format!("({})", parts.join(" OR "))
+            }
+            MetricFilter::Condition(cond) => {
+                let lhs = if cond.abs {
+                    format!("abs({})", cond.metric)
+                } else {
+                    cond.metric.clone()
+                };
+                format!("{} {} {}", lhs, cond.op.to_sql(), params.bind("Float64", cond.value))
             }
         }
+    }
 
-        clauses
+    /// ` FROM ... WHERE ... GROUP BY ...`, shared by the data and count queries.
+    fn build_grouped_source(
+        &self,
+        params: &mut QueryParams,
+        periods: Option<&(String, String)>,
+    ) -> String {
+        let mut sql = String::new();
+
+        // FROM and WHERE clauses
+        sql.push_str(" FROM ");
+        match self.top_n {
+            Some(ref top) => sql.push_str(&self.build_top_n_source(top, params, periods)),
+            None => {
+                sql.push_str(&self.source_sql(params));
+                sql.push_str(&Self::where_sql(&self.build_where_clauses(params, periods)));
+            }
+        }
+
+        // GROUP BY clause
+        let group_cols = self
+            .dimensions
+            .iter()
+            .map(|d| d.to_column())
+            .collect::<Vec<_>>()
+            .join(", ");
+        sql.push_str(" GROUP BY ");
+        sql.push_str(&match self.totals {
+            TotalsMode::None => group_cols,
+            TotalsMode::Grand => format!("GROUPING SETS (({}), ())", group_cols),
+            TotalsMode::Rollup => format!("ROLLUP({})", group_cols),
+            TotalsMode::Cube => format!("CUBE({})", group_cols),
+        });
+
+        sql
+    }
+
+    /// Source rows for a top-N pivot, with the filters applied.
+    ///
+    /// The top values are chosen per parent group with `LIMIT n BY`. Rows outside them
+    /// are relabelled [`OTHER_BUCKET`] before aggregation, rather than summed from the
+    /// truncated groups, so every metric and total over the remainder stays exact.
+    fn build_top_n_source(
+        &self,
+        top: &TopN,
+        params: &mut QueryParams,
+        periods: Option<&(String, String)>,
+    ) -> String {
+        let table = self.source_sql(params);
+        let column = top.dimension.to_column();
+        let position = self
+            .dimensions
+            .iter()
+            .position(|d| *d == top.dimension)
+            .expect("top_n dimension is a row dimension");
+        let parents: Vec<&str> = self.dimensions[..position]
+            .iter()
+            .map(|d| d.to_expr())
+            .collect();
+        let keys: Vec<&str> = parents.iter().copied().chain([column]).collect();
+
+        let aggregate = self
+            .source
+            .aggregation(&top.by)
+            .unwrap_or_else(|| top.by.to_aggregation());
+        let rank = if top.abs { format!("abs({})", aggregate) } else { aggregate };
+        let limit = if parents.is_empty() {
+            format!("LIMIT {}", top.n)
+        } else {
+            format!("LIMIT {} BY {}", top.n, parents.join(", "))
+        };
+
+        let top_query = format!(
+            "SELECT {} FROM {}{} GROUP BY {} ORDER BY {} DESC {}",
+            keys.join(", "),
+            table,
+            Self::where_sql(&self.build_where_clauses(params, periods)),
+            keys.join(", "),
+            rank,
+            limit
+        );
+        let in_top = match keys.as_slice() {
+            [key] => format!("{} IN ({})", key, top_query),
+            _ => format!("({}) IN ({})", keys.join(", "), top_query),
+        };
+
+        let mut where_clauses = self.build_where_clauses(params, periods);
+        if top.other {
+            format!(
+                "(SELECT * REPLACE (if({}, {}, {}) AS {}) FROM {}{})",
+                in_top,
+                column,
+                params.bind("String", OTHER_BUCKET),
+                column,
+                table,
+                Self::where_sql(&where_clauses)
+            )
+        } else {
+            where_clauses.push(in_top);
+            format!("{}{}", table, Self::where_sql(&where_clauses))
+        }
     }
 
-    fn escape_string(s: &str) -> String {
-        // For date strings, only allow date characters
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            return s.to_string();
+    /// `FROM` target: the planned source, converted to the reporting currency if one is set.
+    fn source_sql(&self, params: &mut QueryParams) -> String {
+        match self.reporting_currency {
+            Some(ref currency) => converted_source(self.source.table(), currency, params),
+            None => self.source.table().to_string(),
         }
-        // For other strings, be more restrictive - only alphanumeric and underscores
-        s.chars()
-            .filter(|c| c.is_alphanumeric() || *c == '_')
-            .collect()
     }
 
-    fn exposure_type_to_string(t: &ExposureType) -> &'static str {
-        match t {
-            ExposureType::Direct => "Direct",
-            ExposureType::Etf => "ETF",
-            ExposureType::Etc => "ETC",
-            ExposureType::Constituent => "Constituent",
+    /// ` WHERE a AND b ...`, or nothing without clauses.
+    fn where_sql(clauses: &[String]) -> String {
+        if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        }
+    }
+
+    /// Filter predicates, validated in [`Self::from_request`], restricted to either
+    /// comparison period if there are any.
+    fn build_where_clauses(
+        &self,
+        params: &mut QueryParams,
+        periods: Option<&(String, String)>,
+    ) -> Vec<String> {
+        let mut clauses: Vec<String> = self
+            .predicates
+            .iter()
+            .map(|p| compile_predicate(p, params).expect("predicates are validated up front"))
+            .collect();
+
+        if let Some((current, prior)) = periods {
+            clauses.insert(0, format!("(({}) OR ({}))", current, prior));
         }
+
+        if let Some((ref start, ref end)) = self.window_range {
+            let start = params.bind("Date", start);
+            clauses.insert(
+                0,
+                format!(
+                    "trade_date >= {} AND trade_date <= {}",
+                    lookback_start(&self.windows, &start),
+                    params.bind("Date", end)
+                ),
+            );
+        }
+
+        clauses
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::response::MetricValue;
+    use crate::query::lookthrough::LOOK_THROUGH_SOURCE;
+    use crate::models::request::{
+        CalculatedMetric, ComparePeriod, ComparisonOp, Condition, ExposureType, MetricCondition,
+        SortSpec,
+    };
 
     #[test]
     fn test_simple_query() {
@@ -243,13 +1088,13 @@ mod tests {
         };
 
         let builder = PivotQueryBuilder::from_request(&req).unwrap();
-        let sql = builder.build();
+        let sql = builder.build().sql;
 
         assert!(sql.contains("SELECT asset_class"));
-        assert!(sql.contains("sum(notional) AS total_notional"));
-        assert!(sql.contains("sum(pnl) AS total_pnl"));
-        assert!(sql.contains("FROM pivot.trades_1d"));
-        assert!(sql.contains("WHERE trade_date = '2024-01-15'"));
+        assert!(sql.contains("sumMerge(notional_state) AS total_notional"));
+        assert!(sql.contains("sumMerge(pnl_state) AS total_pnl"));
+        assert!(sql.contains("FROM pivot.trades_1d_rollup"));
+        assert!(sql.contains("WHERE trade_date = {p0:Date}"));
         assert!(sql.contains("GROUP BY asset_class"));
     }
 
@@ -266,9 +1111,9 @@ mod tests {
         };
 
         let builder = PivotQueryBuilder::from_request(&req).unwrap();
-        let sql = builder.build();
+        let sql = builder.build().sql;
 
-        assert!(sql.contains("exposure_type IN ('Direct', 'ETF')"));
+        assert!(sql.contains("exposure_type IN ({p0:String}, {p1:String})"));
     }
 
     #[test]
@@ -285,7 +1130,7 @@ mod tests {
         };
 
         let builder = PivotQueryBuilder::from_request(&req).unwrap();
-        let sql = builder.build();
+        let sql = builder.build().sql;
 
         assert!(sql.contains("SELECT portfolio_manager_id, asset_class, symbol"));
         assert!(sql.contains("GROUP BY portfolio_manager_id, asset_class, symbol"));
@@ -293,6 +1138,49 @@ mod tests {
 
     #[test]
     fn test_sql_injection_prevention() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::AssetClass],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                symbol: Some(vec!["BRK.B'; DROP TABLE trades_1d; --".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let query = builder.build();
+
+        assert!(!query.sql.contains("DROP TABLE"));
+        assert!(!query.sql.contains(";"));
+        assert_eq!(query.params.get("p0"), Some("BRK.B'; DROP TABLE trades_1d; --"));
+    }
+
+    #[test]
+    fn test_filter_values_are_bound_verbatim() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                symbol: Some(vec!["BRK.B".to_string()]),
+                desk: Some(vec!["Fixed Income".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+
+        assert!(query.sql.contains("symbol IN ({p1:String})"));
+        assert!(query.sql.contains("desk IN ({p2:String})"));
+        assert_eq!(query.params.get("p0"), Some("2024-01-15"));
+        assert_eq!(query.params.get("p1"), Some("BRK.B"));
+        assert_eq!(query.params.get("p2"), Some("Fixed Income"));
+    }
+
+    #[test]
+    fn test_invalid_trade_date_rejected() {
         let req = PivotRequest {
             dimensions: vec![Dimension::AssetClass],
             metrics: vec![Metric::Notional],
@@ -303,10 +1191,726 @@ mod tests {
             ..Default::default()
         };
 
+        assert!(matches!(
+            PivotQueryBuilder::from_request(&req),
+            Err(ApiError::QueryValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_uncovered_query_reads_trades() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional, Metric::Fees],
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+
+        assert_eq!(builder.source(), SourceTable::Trades);
+        assert!(sql.contains("sum(notional) AS total_notional"));
+        assert!(sql.contains("FROM pivot.trades_1d GROUP BY desk ORDER BY desk ASC"));
+    }
+
+    #[test]
+    fn test_reporting_currency_converts_before_aggregation() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Book],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                ..Default::default()
+            },
+            reporting_currency: Some("EUR".to_string()),
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let query = builder.build();
+
+        // Covered by the rollup, which can't be converted per trade
+        assert_eq!(builder.source(), SourceTable::Trades);
+        assert!(query.sql.contains("sum(notional) AS total_notional FROM (SELECT * EXCEPT (fx_raw, fx_rate)"));
+        assert!(query.sql.contains("FROM pivot.trades_1d AS t LEFT JOIN"));
+        assert!(query.params.to_http_pairs().iter().any(|(_, v)| v == "EUR"));
+
+        let mut bad = req.clone();
+        bad.reporting_currency = Some("eur".to_string());
+        assert!(PivotQueryBuilder::from_request(&bad).is_err());
+    }
+
+    #[test]
+    fn test_look_through_reads_constituents_at_query_time() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Symbol],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                underlying_symbol: Some(vec!["AAPL".to_string()]),
+                ..Default::default()
+            },
+            look_through: true,
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+
+        // Filters apply to the constituent legs, not the traded symbol
+        assert_eq!(builder.source(), SourceTable::LookThrough);
+        assert!(sql.contains(&format!(
+            "FROM {} WHERE trade_date = {{p0:Date}} AND underlying_symbol IN ({{p1:String}})",
+            LOOK_THROUGH_SOURCE.as_str()
+        )));
+    }
+
+    #[test]
+    fn test_keyset_cursor_with_null_sort_values() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::PnlBps],
+            sort: vec![SortSpec {
+                field: "pnl_bps".to_string(),
+                direction: SortDirection::Desc,
+                nulls: None,
+            }],
+            ..Default::default()
+        };
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+
+        // A page ending on a value still reaches the NULL ratios sorted after it
+        let row = |metrics: Vec<(String, MetricValue)>| PivotRow {
+            dimensions: [("desk".to_string(), serde_json::json!("FX"))].into(),
+            metrics: metrics.into_iter().collect(),
+            cells: vec![],
+            aggregated: vec![],
+            comparison: Default::default(),
+        };
+        let last = row(vec![("pnl_bps".to_string(), MetricValue::Float(2.5))]);
+        req.cursor = builder.next_cursor(&last);
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert!(query.sql.contains(
+            "HAVING ((pnl_bps < {p1:Float64} OR isNull(pnl_bps)) \
+             OR (pnl_bps = {p1:Float64} AND (desk > {p0:String})))"
+        ));
+
+        // A page ending on a NULL ratio carries on through the remaining NULLs
+        req.cursor = builder.next_cursor(&row(vec![]));
+        assert!(req.cursor.is_some());
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert!(query.sql.contains("HAVING ((isNull(pnl_bps) AND (desk > {p0:String})))"));
+    }
+
+    #[test]
+    fn test_look_through_counts_trades_and_scales_slippage_once() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::TradeCount, Metric::Slippage],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let top_level = PivotQueryBuilder::from_request(&req).unwrap().build().sql;
+        req.look_through = true;
+        let look_through = PivotQueryBuilder::from_request(&req).unwrap().build().sql;
+
+        assert!(top_level.contains("count() AS trade_count, sum(slippage) AS total_slippage"));
+        // One trade split into legs still counts once, and its legs' slippage adds
+        // back up to the trade's because it is scaled by the leg weight
+        assert!(look_through
+            .contains("uniqExact(trade_id) AS trade_count, sum(slippage) AS total_slippage"));
+        assert!(LOOK_THROUGH_SOURCE.contains("slippage * leg.2 AS slippage"));
+    }
+
+    #[test]
+    fn test_rollup_totals() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk, Dimension::Book],
+            metrics: vec![Metric::Notional],
+            totals: TotalsMode::Rollup,
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+
+        assert!(sql.contains("grouping(desk, book) AS grouping_id"));
+        assert!(sql.contains("GROUP BY ROLLUP(desk, book)"));
+        assert!(builder.aggregated_dimensions(0).is_empty());
+        assert_eq!(builder.aggregated_dimensions(0b01), vec!["book"]);
+        assert_eq!(builder.aggregated_dimensions(0b11), vec!["desk", "book"]);
+    }
+
+    #[test]
+    fn test_grand_total_only() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional],
+            totals: TotalsMode::Grand,
+            ..Default::default()
+        };
+
+        let sql = PivotQueryBuilder::from_request(&req).unwrap().build().sql;
+
+        assert!(sql.contains("GROUP BY GROUPING SETS ((desk), ())"));
+    }
+
+    #[test]
+    fn test_cross_tab() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            columns: vec![Dimension::ExposureType],
+            metrics: vec![Metric::Notional],
+            max_columns: 2,
+            ..Default::default()
+        };
+
+        let mut builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let header_sql = builder.build_header_query().unwrap().sql;
+        assert!(header_sql.contains("SELECT DISTINCT exposure_type FROM pivot.trades_1d"));
+        assert!(header_sql.contains("LIMIT 3"));
+
+        let truncated = builder.set_column_headers(vec![
+            vec!["Direct".to_string()],
+            vec!["ETF".to_string()],
+            vec!["ETC".to_string()],
+        ]);
+        assert!(truncated);
+        assert_eq!(builder.column_headers().len(), 2);
+
+        let query = builder.build();
+        assert!(query
+            .sql
+            .contains("sumIf(notional, toString(exposure_type) = {p1:String}) AS c1__total_notional"));
+        assert_eq!(query.params.get("p0"), Some("Direct"));
+        assert!(query.sql.contains("GROUP BY desk "));
+        assert_eq!(parse_cell_alias("c1__total_notional"), Some((1, "total_notional")));
+        assert_eq!(parse_cell_alias("country"), None);
+    }
+
+    #[test]
+    fn test_dimension_on_both_axes_rejected() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            columns: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            PivotQueryBuilder::from_request(&req),
+            Err(ApiError::QueryValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_count_query() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk, Dimension::Strategy],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                ..Default::default()
+            },
+            limit: 10,
+            offset: 20,
+            ..Default::default()
+        };
+
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build_count_query();
+
+        assert_eq!(
+            query.sql,
+            "SELECT count() AS total_rows FROM (SELECT desk, strategy FROM pivot.trades_1d \
+             WHERE trade_date = {p0:Date} GROUP BY desk, strategy)"
+        );
+    }
+
+    #[test]
+    fn test_keyset_cursor() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl],
+            sort: vec![SortSpec {
+                field: "total_pnl".to_string(),
+                direction: SortDirection::Desc,
+                nulls: None,
+            }],
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+        assert!(sql.contains("ORDER BY total_pnl DESC, desk ASC"));
+
+        let row = PivotRow {
+            dimensions: [("desk".to_string(), serde_json::json!("FX"))].into(),
+            metrics: [("total_pnl".to_string(), MetricValue::Float(1250.5))].into(),
+            cells: vec![],
+            aggregated: vec![],
+            comparison: Default::default(),
+        };
+        req.cursor = builder.next_cursor(&row);
+        assert!(req.cursor.is_some());
+
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert!(query.sql.contains(
+            "HAVING (total_pnl < {p1:Float64} OR (total_pnl = {p1:Float64} AND (desk > {p0:String})))"
+        ));
+        assert_eq!(query.params.get("p0"), Some("FX"));
+        assert_eq!(query.params.get("p1"), Some("1250.5"));
+
+        req.offset = 100;
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+
+        // A cursor from another sort is an error, not silently the first page
+        req.offset = 0;
+        req.cursor = Some(encode_cursor(&[Some("FX".to_string())]));
+        assert!(matches!(
+            PivotQueryBuilder::from_request(&req),
+            Err(ApiError::QueryValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_multi_key_sort() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk, Dimension::Book],
+            metrics: vec![Metric::Pnl],
+            sort: vec![
+                SortSpec {
+                    field: "book".to_string(),
+                    direction: SortDirection::Asc,
+                    nulls: None,
+                },
+                SortSpec {
+                    field: "total_pnl".to_string(),
+                    direction: SortDirection::Desc,
+                    nulls: Some(NullsOrder::Last),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let sql = PivotQueryBuilder::from_request(&req).unwrap().build().sql;
+
+        assert!(sql.contains("ORDER BY book ASC, total_pnl DESC NULLS LAST, desk ASC LIMIT"));
+    }
+
+    #[test]
+    fn test_invalid_sort_field_rejected() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl],
+            sort: vec![SortSpec {
+                field: "total_pnl; DROP TABLE trades_1d".to_string(),
+                direction: SortDirection::Desc,
+                nulls: None,
+            }],
+            ..Default::default()
+        };
+
+        match PivotQueryBuilder::from_request(&req) {
+            Err(ApiError::QueryValidation(msg)) => {
+                assert!(msg.contains("Valid fields: [\"desk\", \"total_pnl\"]"));
+            }
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn test_single_sort_object_still_accepted() {
+        let req: PivotRequest = serde_json::from_str(
+            r#"{"dimensions":["desk"],"metrics":["pnl"],"sort":{"field":"total_pnl"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(req.sort.len(), 1);
+        assert_eq!(req.sort[0].direction, SortDirection::Desc);
+    }
+
+    #[test]
+    fn test_ratio_metric_in_rollup_totals() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Book],
+            metrics: vec![Metric::PnlBps],
+            totals: TotalsMode::Rollup,
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+
+        assert_eq!(builder.source(), SourceTable::Rollup);
+        assert!(sql.contains(
+            "sumMerge(pnl_state) / nullIf(sumMerge(notional_state), 0) * 10000 AS pnl_bps"
+        ));
+        assert!(builder.is_metric_alias("pnl_bps"));
+        assert!(!builder.is_metric_alias("book"));
+    }
+
+    #[test]
+    fn test_calculated_metric() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            calculated_metrics: vec![CalculatedMetric {
+                name: "net_bps".to_string(),
+                expression: "(pnl - fees) / notional * 10000".to_string(),
+            }],
+            sort: vec![SortSpec {
+                field: "net_bps".to_string(),
+                direction: SortDirection::Desc,
+                nulls: None,
+            }],
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let sql = builder.build().sql;
+
+        assert!(sql.contains(
+            "(((sum(pnl) - sum(fees)) / nullIf(sum(notional), 0)) * 10000) AS net_bps"
+        ));
+        assert!(sql.contains("ORDER BY net_bps DESC, desk ASC"));
+        assert!(builder.is_metric_alias("net_bps"));
+    }
+
+    #[test]
+    fn test_calculated_metric_name_clash_rejected() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            calculated_metrics: vec![CalculatedMetric {
+                name: "total_pnl".to_string(),
+                expression: "pnl".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_calculated_metric_named_after_source_column_rejected() {
+        let request = |name: &str| PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            calculated_metrics: vec![CalculatedMetric {
+                name: name.to_string(),
+                expression: "(pnl - fees) / notional * 10000".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        assert!(PivotQueryBuilder::from_request(&request("net_bps")).is_ok());
+        for name in ["pnl", "notional", "metric_1", "trade_id"] {
+            assert!(PivotQueryBuilder::from_request(&request(name)).is_err(), "{} accepted", name);
+        }
+    }
+
+    #[test]
+    fn test_having_filters() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl, Metric::TradeCount],
+            having: vec![MetricFilter::Or {
+                or: vec![
+                    MetricFilter::Condition(MetricCondition {
+                        metric: "total_pnl".to_string(),
+                        op: ComparisonOp::Gt,
+                        value: 1_000_000.0,
+                        abs: true,
+                    }),
+                    MetricFilter::Condition(MetricCondition {
+                        metric: "trade_count".to_string(),
+                        op: ComparisonOp::Gte,
+                        value: 10.0,
+                        abs: false,
+                    }),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        let query = builder.build();
+        assert!(query.sql.contains(
+            "GROUP BY desk HAVING (abs(total_pnl) > {p0:Float64} OR trade_count >= {p1:Float64})"
+        ));
+        assert_eq!(query.params.get("p0"), Some("1000000"));
+
+        let count_sql = builder.build_count_query().sql;
+        assert!(count_sql.contains("SELECT desk, sum(pnl) AS total_pnl, count() AS trade_count"));
+        assert!(count_sql.contains("HAVING (abs(total_pnl) > {p0:Float64}"));
+    }
+
+    #[test]
+    fn test_having_unrequested_metric_rejected() {
+        let req: PivotRequest = serde_json::from_str(
+            r#"{"dimensions":["desk"],"metrics":["pnl"],
+                "having":[{"metric":"total_notional","op":"gt","value":0}]}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            PivotQueryBuilder::from_request(&req),
+            Err(ApiError::QueryValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_generic_predicates() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Strategy],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                parent_symbol: Some(vec!["SPY".to_string()]),
+                predicates: vec![
+                    Predicate::new(Condition::NotIn {
+                        dimension: Dimension::Venue,
+                        values: vec![serde_json::json!("OTC")],
+                    }),
+                    Predicate {
+                        condition: Condition::Between {
+                            field: "notional".to_string(),
+                            min: Some(serde_json::json!(1000)),
+                            max: None,
+                        },
+                        negate: true,
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert!(query.sql.contains(
+            "WHERE trade_date = {p0:Date} AND parent_symbol IN ({p1:String}) \
+             AND venue NOT IN ({p2:String}) AND NOT (notional >= {p3:Float64})"
+        ));
+        assert_eq!(query.params.get("p3"), Some("1000"));
+    }
+
+    #[test]
+    fn test_invalid_predicate_rejected() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                predicates: vec![Predicate::new(Condition::In {
+                    dimension: Dimension::PortfolioId,
+                    values: vec![serde_json::json!("abc")],
+                })],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_period_comparison() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                desk: Some(vec!["FX".to_string()]),
+                ..Default::default()
+            },
+            sort: vec![SortSpec {
+                field: "total_pnl__change".to_string(),
+                direction: SortDirection::Desc,
+                nulls: None,
+            }],
+            compare: Some(ComparePeriod {
+                trade_date: Some("2024-01-12".to_string()),
+                trade_date_range: None,
+            }),
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        assert!(builder.is_comparison());
+        let query = builder.build();
+
+        assert!(query.sql.starts_with(
+            "SELECT desk, sumIf(pnl, trade_date = {p0:Date}) AS total_pnl, \
+             sumIf(pnl, trade_date = {p1:Date}) AS total_pnl__prior, \
+             total_pnl - total_pnl__prior AS total_pnl__change, \
+             (total_pnl - total_pnl__prior) / nullIf(abs(total_pnl__prior), 0) * 100 \
+             AS total_pnl__change_pct FROM pivot.trades_1d \
+             WHERE ((trade_date = {p0:Date}) OR (trade_date = {p1:Date})) \
+             AND desk IN ({p2:String}) GROUP BY desk"
+        ));
+        assert!(query.sql.contains("ORDER BY total_pnl__change DESC"));
+        assert_eq!(query.params.get("p1"), Some("2024-01-12"));
+
+        assert_eq!(
+            parse_comparison_alias("total_pnl__change_pct"),
+            Some(("total_pnl", ComparisonPart::ChangePct))
+        );
+    }
+
+    #[test]
+    fn test_comparison_needs_both_periods() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl],
+            compare: Some(ComparePeriod {
+                trade_date: Some("2024-01-12".to_string()),
+                trade_date_range: None,
+            }),
+            ..Default::default()
+        };
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_window_metrics() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk, Dimension::TradeDate],
+            metrics: vec![Metric::Pnl],
+            window_metrics: vec![
+                WindowMetric { kind: WindowKind::Mtd, metric: Metric::Pnl },
+                WindowMetric { kind: WindowKind::Rank, metric: Metric::Pnl },
+            ],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let builder = PivotQueryBuilder::from_request(&req).unwrap();
+        assert!(builder.is_metric_alias("total_pnl_mtd"));
+        let query = builder.build();
+
+        assert_eq!(
+            query.sql,
+            "SELECT * FROM (SELECT desk, trade_date, sum(pnl) AS total_pnl, \
+             sum(sum(pnl)) OVER (PARTITION BY desk, toStartOfMonth(trade_date) ORDER BY trade_date \
+             ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS total_pnl_mtd, \
+             rank() OVER (PARTITION BY desk, trade_date >= {p0:Date} ORDER BY sum(pnl) DESC) \
+             AS total_pnl_rank \
+             FROM pivot.trades_1d \
+             WHERE trade_date >= toStartOfMonth({p1:Date}) AND trade_date <= {p2:Date} \
+             GROUP BY desk, trade_date) \
+             WHERE trade_date >= {p3:Date} AND trade_date <= {p4:Date} \
+             ORDER BY desk ASC, trade_date ASC LIMIT 100"
+        );
+        // Month-to-date lookback days rank apart from the requested day
+        assert_eq!(query.params.get("p0"), Some("2024-01-15"));
+        assert_eq!(query.params.get("p3"), Some("2024-01-15"));
+    }
+
+    #[test]
+    fn test_invalid_window_metrics_rejected() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Pnl],
+            window_metrics: vec![WindowMetric { kind: WindowKind::Running, metric: Metric::Pnl }],
+            ..Default::default()
+        };
+        // Cumulative windows need trade_date on the rows
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+
+        req.dimensions.push(Dimension::TradeDate);
+        assert!(PivotQueryBuilder::from_request(&req).is_ok());
+
+        req.window_metrics[0].metric = Metric::PnlBps;
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+
+        req.window_metrics[0] = WindowMetric { kind: WindowKind::Rank, metric: Metric::Pnl };
+        req.totals = TotalsMode::Rollup;
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_top_n_with_other_bucket() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk, Dimension::Symbol],
+            metrics: vec![Metric::Notional],
+            filters: PivotFilters {
+                trade_date: Some("2024-01-15".to_string()),
+                ..Default::default()
+            },
+            top_n: Some(TopN {
+                dimension: Dimension::Symbol,
+                n: 10,
+                by: Metric::Notional,
+                abs: true,
+                other: true,
+            }),
+            ..Default::default()
+        };
+
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert_eq!(
+            query.sql,
+            "SELECT desk, symbol, sum(notional) AS total_notional \
+             FROM (SELECT * REPLACE (if((desk, symbol) IN (\
+             SELECT desk, symbol FROM pivot.trades_1d WHERE trade_date = {p0:Date} \
+             GROUP BY desk, symbol ORDER BY abs(sum(notional)) DESC LIMIT 10 BY desk\
+             ), symbol, {p2:String}) AS symbol) FROM pivot.trades_1d WHERE trade_date = {p1:Date}) \
+             GROUP BY desk, symbol ORDER BY desk ASC, symbol ASC LIMIT 100"
+        );
+        assert_eq!(query.params.get("p2"), Some(OTHER_BUCKET));
+
+        // Without the bucket, the remainder is filtered out instead
+        req.top_n.as_mut().unwrap().other = false;
+        let query = PivotQueryBuilder::from_request(&req).unwrap().build();
+        assert!(query.sql.contains(
+            "FROM pivot.trades_1d WHERE trade_date = {p1:Date} AND (desk, symbol) IN (SELECT"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_top_n_rejected() {
+        let mut req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::Notional],
+            top_n: Some(TopN {
+                dimension: Dimension::Symbol,
+                n: 10,
+                by: Metric::Notional,
+                abs: false,
+                other: true,
+            }),
+            ..Default::default()
+        };
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+
+        req.dimensions = vec![Dimension::FundId];
+        req.top_n.as_mut().unwrap().dimension = Dimension::FundId;
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+
+        req.dimensions = vec![Dimension::Symbol];
+        req.top_n.as_mut().unwrap().dimension = Dimension::Symbol;
+        req.top_n.as_mut().unwrap().n = 0;
+        assert!(PivotQueryBuilder::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_integer_metrics() {
+        let req = PivotRequest {
+            dimensions: vec![Dimension::Desk],
+            metrics: vec![Metric::TradeCount, Metric::DistinctAccounts, Metric::MedianSlippage],
+            window_metrics: vec![WindowMetric { kind: WindowKind::Rank, metric: Metric::Pnl }],
+            ..Default::default()
+        };
         let builder = PivotQueryBuilder::from_request(&req).unwrap();
-        let sql = builder.build();
 
-        assert!(!sql.contains("DROP TABLE"));
-        assert!(!sql.contains(";"));
+        assert!(builder.is_integer_metric("trade_count"));
+        assert!(builder.is_integer_metric("distinct_accounts"));
+        assert!(builder.is_integer_metric("total_pnl_rank"));
+        assert!(!builder.is_integer_metric("median_slippage"));
+        assert!(builder
+            .build()
+            .sql
+            .contains("uniqExact(account_id) AS distinct_accounts, quantile(0.5)(slippage)"));
     }
 }
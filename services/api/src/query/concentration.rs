@@ -0,0 +1,190 @@
+use crate::config::ConcentrationConfig;
+use crate::error::ApiError;
+use crate::models::request::ConcentrationQuery;
+use crate::models::response::ConcentrationStatus;
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+use crate::query::SourceTable;
+
+/// Largest single-name exposures, direct and via ETF/ETC, as a share of gross.
+pub struct ConcentrationQueryBuilder {
+    query: ConcentrationQuery,
+    warn_pct: f64,
+    breach_pct: f64,
+}
+
+impl ConcentrationQueryBuilder {
+    pub fn from_query(
+        query: &ConcentrationQuery,
+        defaults: &ConcentrationConfig,
+    ) -> Result<Self, ApiError> {
+        validate_date("trade_date", &query.trade_date)?;
+
+        let warn_pct = query.warn_pct.unwrap_or(defaults.warn_pct);
+        let breach_pct = query.breach_pct.unwrap_or(defaults.breach_pct);
+        for (name, pct) in [("warn_pct", warn_pct), ("breach_pct", breach_pct)] {
+            if !(pct > 0.0 && pct <= 100.0) {
+                return Err(ApiError::QueryValidation(format!(
+                    "{} must be in (0, 100], got {}",
+                    name, pct
+                )));
+            }
+        }
+        if warn_pct > breach_pct {
+            return Err(ApiError::QueryValidation(
+                "warn_pct must not exceed breach_pct".to_string(),
+            ));
+        }
+        if query.limit == 0 || query.limit > 1000 {
+            return Err(ApiError::QueryValidation(
+                "limit must be between 1 and 1000".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            query: query.clone(),
+            warn_pct,
+            breach_pct,
+        })
+    }
+
+    pub fn warn_pct(&self) -> f64 {
+        self.warn_pct
+    }
+
+    pub fn breach_pct(&self) -> f64 {
+        self.breach_pct
+    }
+
+    pub fn source(&self) -> SourceTable {
+        if self.query.look_through {
+            SourceTable::LookThrough
+        } else {
+            SourceTable::Trades
+        }
+    }
+
+    /// Net exposure per underlying, largest first. Gross is taken over every name in
+    /// scope before the limit applies, so percentages don't depend on it.
+    ///
+    /// Looked through, the part of an ETF/ETC its basket doesn't explain stays on a
+    /// residual leg under the composite's own symbol and counts as `residual_exposure`,
+    /// so composites with missing or partial baskets still count towards gross.
+    pub fn build(&self) -> BoundQuery {
+        let mut params = QueryParams::new();
+        let mut conditions =
+            vec![format!("trade_date = {}", params.bind("Date", &self.query.trade_date))];
+        if !self.query.look_through {
+            conditions.push("exposure_type IN ('Direct', 'Constituent')".to_string());
+        }
+        if let Some(fund_id) = self.query.fund_id {
+            conditions.push(format!("fund_id = {}", params.bind("UInt32", fund_id)));
+        }
+        if let Some(pm_id) = self.query.portfolio_manager_id {
+            conditions.push(format!("portfolio_manager_id = {}", params.bind("UInt32", pm_id)));
+        }
+
+        let sql = format!(
+            "SELECT underlying_symbol, \
+             sumIf(notional, exposure_type = 'Direct') AS direct_exposure, \
+             sumIf(notional, exposure_type = 'Constituent') AS etf_exposure, \
+             sumIf(notional, exposure_type IN ('ETF', 'ETC')) AS residual_exposure, \
+             sum(notional) AS total_exposure, \
+             sum(abs(sum(notional))) OVER () AS gross_exposure \
+             FROM {} WHERE {} \
+             GROUP BY underlying_symbol \
+             ORDER BY abs(total_exposure) DESC, underlying_symbol \
+             LIMIT {}",
+            self.source().table(),
+            conditions.join(" AND "),
+            self.query.limit
+        );
+
+        BoundQuery::new(sql, params)
+    }
+
+    /// Flag for a name holding `pct_of_gross` percent of gross exposure.
+    pub fn status(&self, pct_of_gross: f64) -> ConcentrationStatus {
+        if pct_of_gross >= self.breach_pct {
+            ConcentrationStatus::Breach
+        } else if pct_of_gross >= self.warn_pct {
+            ConcentrationStatus::Warn
+        } else {
+            ConcentrationStatus::Ok
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query() -> ConcentrationQuery {
+        ConcentrationQuery {
+            trade_date: "2024-01-15".to_string(),
+            fund_id: Some(7),
+            portfolio_manager_id: None,
+            warn_pct: None,
+            breach_pct: None,
+            look_through: false,
+            limit: 50,
+            cache_bypass: false,
+        }
+    }
+
+    fn defaults() -> ConcentrationConfig {
+        ConcentrationConfig { warn_pct: 5.0, breach_pct: 10.0 }
+    }
+
+    #[test]
+    fn test_concentration_query() {
+        let builder = ConcentrationQueryBuilder::from_query(&query(), &defaults()).unwrap();
+        let bound = builder.build();
+
+        assert!(bound.sql.contains(
+            "FROM pivot.trades_1d WHERE trade_date = {p0:Date} \
+             AND exposure_type IN ('Direct', 'Constituent') AND fund_id = {p1:UInt32} \
+             GROUP BY underlying_symbol"
+        ));
+        assert!(bound.sql.contains("sumIf(notional, exposure_type = 'Constituent') AS etf_exposure"));
+        assert_eq!(bound.params.get("p1"), Some("7"));
+    }
+
+    #[test]
+    fn test_look_through_keeps_residual_legs() {
+        let mut q = query();
+        q.look_through = true;
+        let bound = ConcentrationQueryBuilder::from_query(&q, &defaults()).unwrap().build();
+
+        // Unexplained composite exposure stays in gross and is reported on its own
+        assert!(!bound.sql.contains("exposure_type IN ('Direct', 'Constituent')"));
+        assert!(bound.sql.contains("WHERE trade_date = {p0:Date} AND fund_id = {p1:UInt32}"));
+        assert!(bound
+            .sql
+            .contains("sumIf(notional, exposure_type IN ('ETF', 'ETC')) AS residual_exposure"));
+    }
+
+    #[test]
+    fn test_concentration_status() {
+        let mut q = query();
+        q.warn_pct = Some(2.5);
+        let builder = ConcentrationQueryBuilder::from_query(&q, &defaults()).unwrap();
+
+        assert_eq!(builder.status(1.0), ConcentrationStatus::Ok);
+        assert_eq!(builder.status(2.5), ConcentrationStatus::Warn);
+        assert_eq!(builder.status(12.0), ConcentrationStatus::Breach);
+    }
+
+    #[test]
+    fn test_invalid_thresholds_rejected() {
+        let mut q = query();
+        q.warn_pct = Some(20.0);
+        assert!(ConcentrationQueryBuilder::from_query(&q, &defaults()).is_err());
+
+        q.warn_pct = Some(-1.0);
+        assert!(ConcentrationQueryBuilder::from_query(&q, &defaults()).is_err());
+
+        q.warn_pct = None;
+        q.breach_pct = Some(f64::NAN);
+        assert!(ConcentrationQueryBuilder::from_query(&q, &defaults()).is_err());
+    }
+}
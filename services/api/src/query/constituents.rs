@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::models::response::{Constituent, ConstituentChange, ConstituentDiffResponse};
+use crate::query::params::{BoundQuery, QueryParams};
+
+/// Weight changes smaller than this are treated as unchanged.
+const WEIGHT_EPSILON: f64 = 1e-9;
+
+/// Whether a constituent version is in effect on `date`.
+///
+/// `expiry_date` is exclusive, so a version expiring on the day its successor takes
+/// effect never overlaps it. Every as-of lookup, look-through included, uses this.
+pub fn effective_on(effective_date: &str, expiry_date: &str, date: &str) -> String {
+    format!(
+        "{} <= {} AND {} < {}",
+        effective_date, date, date, expiry_date
+    )
+}
+
+/// Constituent versions matching the filters, or only those in effect on `as_of`.
+pub fn basket_query(
+    parent_symbol: Option<&str>,
+    constituent_symbol: Option<&str>,
+    as_of: Option<&str>,
+) -> BoundQuery {
+    let mut params = QueryParams::new();
+    let mut conditions = Vec::new();
+
+    if let Some(ps) = parent_symbol {
+        conditions.push(format!("parent_symbol = {}", params.bind("String", ps)));
+    }
+    if let Some(cs) = constituent_symbol {
+        conditions.push(format!("constituent_symbol = {}", params.bind("String", cs)));
+    }
+    if let Some(date) = as_of {
+        let date = params.bind("Date", date);
+        conditions.push(effective_on("effective_date", "expiry_date", &date));
+    }
+
+    // FINAL collapses re-inserted versions to the latest created_at. The string
+    // projections get their own aliases so the date filter still sees Date columns.
+    let mut sql = "SELECT parent_symbol, constituent_symbol, weight, shares_per_unit, \
+                   toString(effective_date) AS effective_date_str, toString(expiry_date) AS expiry_date_str \
+                   FROM pivot.constituents FINAL"
+        .to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY parent_symbol, effective_date, weight DESC");
+
+    BoundQuery::new(sql, params)
+}
+
+/// Names added, removed and reweighted between two compositions of one basket.
+pub fn diff_baskets(
+    parent_symbol: &str,
+    from_date: &str,
+    to_date: &str,
+    from: Vec<Constituent>,
+    to: Vec<Constituent>,
+) -> ConstituentDiffResponse {
+    let mut previous: HashMap<String, Constituent> = from
+        .into_iter()
+        .map(|c| (c.constituent_symbol.clone(), c))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut reweighted = Vec::new();
+    let mut unchanged = 0;
+
+    for current in to {
+        match previous.remove(&current.constituent_symbol) {
+            None => added.push(current),
+            Some(old) if (current.weight - old.weight).abs() > WEIGHT_EPSILON => {
+                reweighted.push(ConstituentChange {
+                    constituent_symbol: current.constituent_symbol,
+                    from_weight: old.weight,
+                    to_weight: current.weight,
+                    change: current.weight - old.weight,
+                })
+            }
+            Some(_) => unchanged += 1,
+        }
+    }
+
+    let mut removed: Vec<Constituent> = previous.into_values().collect();
+    removed.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    reweighted.sort_by(|a, b| b.change.abs().total_cmp(&a.change.abs()));
+
+    ConstituentDiffResponse {
+        parent_symbol: parent_symbol.to_string(),
+        from_date: from_date.to_string(),
+        to_date: to_date.to_string(),
+        added,
+        removed,
+        reweighted,
+        unchanged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constituent(symbol: &str, weight: f64) -> Constituent {
+        Constituent {
+            parent_symbol: "SPY".to_string(),
+            constituent_symbol: symbol.to_string(),
+            weight,
+            shares_per_unit: 0.1,
+            effective_date: "2024-01-01".to_string(),
+            expiry_date: "2099-12-31".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_as_of_basket_query() {
+        let query = basket_query(Some("SPY"), None, Some("2024-03-01"));
+
+        assert!(query.sql.ends_with(
+            "FROM pivot.constituents FINAL \
+             WHERE parent_symbol = {p0:String} \
+             AND effective_date <= {p1:Date} AND {p1:Date} < expiry_date \
+             ORDER BY parent_symbol, effective_date, weight DESC"
+        ));
+        // Aliases must not shadow the Date columns the filter compares against
+        assert!(query.sql.contains("toString(effective_date) AS effective_date_str"));
+        assert!(!query.sql.contains("AS effective_date,") && !query.sql.contains("AS expiry_date "));
+        assert_eq!(query.params.get("p1"), Some("2024-03-01"));
+    }
+
+    #[test]
+    fn test_diff_baskets() {
+        let diff = diff_baskets(
+            "SPY",
+            "2024-01-01",
+            "2024-04-01",
+            vec![
+                constituent("AAPL", 0.07),
+                constituent("MSFT", 0.065),
+                constituent("JPM", 0.015),
+            ],
+            vec![
+                constituent("AAPL", 0.07),
+                constituent("MSFT", 0.06),
+                constituent("NVDA", 0.05),
+            ],
+        );
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].constituent_symbol, "NVDA");
+        assert_eq!(diff.removed[0].constituent_symbol, "JPM");
+        assert_eq!(diff.reweighted.len(), 1);
+        assert_eq!(diff.reweighted[0].constituent_symbol, "MSFT");
+        assert!((diff.reweighted[0].change + 0.005).abs() < 1e-12);
+        assert_eq!(diff.unchanged, 1);
+    }
+}
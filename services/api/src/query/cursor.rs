@@ -0,0 +1,199 @@
+use crate::error::ApiError;
+use crate::query::params::QueryParams;
+
+/// One component of a keyset ordering: an expression, its ClickHouse type and direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyColumn {
+    pub expr: String,
+    pub ty: &'static str,
+    pub descending: bool,
+    /// Explicit `NULLS FIRST`/`NULLS LAST`; ClickHouse's default, last, when `None`.
+    pub nulls_first: Option<bool>,
+    /// Whether the key can be NULL, e.g. a ratio over a zero denominator.
+    pub nullable: bool,
+}
+
+impl KeyColumn {
+    /// The `ORDER BY` term for this key.
+    pub fn order_term(&self) -> String {
+        let mut term = format!("{} {}", self.expr, if self.descending { "DESC" } else { "ASC" });
+        match self.nulls_first {
+            Some(true) => term.push_str(" NULLS FIRST"),
+            Some(false) => term.push_str(" NULLS LAST"),
+            None => {}
+        }
+        term
+    }
+}
+
+/// Encodes the sort-key values of the last returned row as an opaque page token.
+/// A `None` value stands for a NULL sort key.
+pub fn encode_cursor(values: &[Option<String>]) -> String {
+    let json = serde_json::to_string(values).unwrap_or_default();
+    json.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<Vec<Option<String>>, ApiError> {
+    let invalid = || ApiError::QueryValidation("Invalid cursor".to_string());
+
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| {
+            let pair = cursor.get(i..i + 2).ok_or_else(invalid)?;
+            u8::from_str_radix(pair, 16).map_err(|_| invalid())
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    serde_json::from_slice(&bytes).map_err(|_| invalid())
+}
+
+/// Predicate selecting rows strictly after `values` in the ordering given by `keys`.
+///
+/// Expands to `k1 > v1 OR (k1 = v1 AND (k2 > v2 OR ...))`, flipping the comparison
+/// for descending keys, so mixed directions work where a tuple comparison would not.
+/// Nullable keys place NULLs where the `ORDER BY` does: a NULL value is followed only
+/// by more NULLs (or, with NULLs first, by every value), and NULLs last follow any value.
+pub fn keyset_predicate(
+    keys: &[KeyColumn],
+    values: &[Option<String>],
+    params: &mut QueryParams,
+) -> Result<String, ApiError> {
+    if keys.len() != values.len() || keys.is_empty() {
+        return Err(ApiError::QueryValidation(
+            "Cursor does not match the requested sort".to_string(),
+        ));
+    }
+
+    let mut predicate = String::new();
+    for (key, value) in keys.iter().zip(values).rev() {
+        let nulls_first = key.nulls_first.unwrap_or(false);
+        let (after, equal) = match value {
+            Some(value) => {
+                let placeholder = params.bind(key.ty, value);
+                let op = if key.descending { "<" } else { ">" };
+                let after = if key.nullable && !nulls_first {
+                    format!("({} {} {} OR isNull({}))", key.expr, op, placeholder, key.expr)
+                } else {
+                    format!("{} {} {}", key.expr, op, placeholder)
+                };
+                (Some(after), format!("{} = {}", key.expr, placeholder))
+            }
+            None if !key.nullable => {
+                return Err(ApiError::QueryValidation(format!(
+                    "Cursor has no value for '{}'",
+                    key.expr
+                )))
+            }
+            None => (
+                nulls_first.then(|| format!("isNotNull({})", key.expr)),
+                format!("isNull({})", key.expr),
+            ),
+        };
+
+        let mut terms: Vec<String> = after.into_iter().collect();
+        if !predicate.is_empty() {
+            terms.push(format!("({} AND ({}))", equal, predicate));
+        }
+        predicate = if terms.is_empty() { "0".to_string() } else { terms.join(" OR ") };
+    }
+
+    Ok(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let values = vec![Some("Equities".to_string()), None, Some("1234.5".to_string())];
+        let cursor = encode_cursor(&values);
+        assert_eq!(decode_cursor(&cursor).unwrap(), values);
+        assert!(decode_cursor("zz").is_err());
+        assert!(decode_cursor("abc").is_err());
+    }
+
+    #[test]
+    fn test_keyset_predicate_mixed_directions() {
+        let keys = vec![
+            KeyColumn {
+                expr: "total_pnl".to_string(),
+                ty: "Float64",
+                descending: true,
+                nulls_first: None,
+                nullable: false,
+            },
+            KeyColumn {
+                expr: "desk".to_string(),
+                ty: "String",
+                descending: false,
+                nulls_first: None,
+                nullable: false,
+            },
+        ];
+        let mut params = QueryParams::new();
+        let values = [Some("10".to_string()), Some("FX".to_string())];
+        let predicate = keyset_predicate(&keys, &values, &mut params).unwrap();
+
+        assert_eq!(
+            predicate,
+            "total_pnl < {p1:Float64} OR (total_pnl = {p1:Float64} AND (desk > {p0:String}))"
+        );
+        assert_eq!(params.get("p1"), Some("10"));
+    }
+
+    fn ratio_then_desk(nulls_first: Option<bool>) -> Vec<KeyColumn> {
+        vec![
+            KeyColumn {
+                expr: "pnl_bps".to_string(),
+                ty: "Float64",
+                descending: true,
+                nulls_first,
+                nullable: true,
+            },
+            KeyColumn {
+                expr: "desk".to_string(),
+                ty: "String",
+                descending: false,
+                nulls_first: None,
+                nullable: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_keyset_predicate_reaches_null_keys_after_values() {
+        let mut params = QueryParams::new();
+        let values = [Some("10".to_string()), Some("FX".to_string())];
+        let predicate = keyset_predicate(&ratio_then_desk(None), &values, &mut params).unwrap();
+
+        // NULLs sort last, so they are still ahead of a page ending on a value
+        assert_eq!(
+            predicate,
+            "(pnl_bps < {p1:Float64} OR isNull(pnl_bps)) \
+             OR (pnl_bps = {p1:Float64} AND (desk > {p0:String}))"
+        );
+    }
+
+    #[test]
+    fn test_keyset_predicate_resumes_inside_null_keys() {
+        let values = [None, Some("FX".to_string())];
+
+        let mut params = QueryParams::new();
+        let predicate = keyset_predicate(&ratio_then_desk(None), &values, &mut params).unwrap();
+        assert_eq!(predicate, "(isNull(pnl_bps) AND (desk > {p0:String}))");
+
+        let mut params = QueryParams::new();
+        let predicate =
+            keyset_predicate(&ratio_then_desk(Some(true)), &values, &mut params).unwrap();
+        assert_eq!(
+            predicate,
+            "isNotNull(pnl_bps) OR (isNull(pnl_bps) AND (desk > {p0:String}))"
+        );
+
+        // Dimensions are never NULL, so a cursor claiming one is forged or stale
+        let mut params = QueryParams::new();
+        let values = [Some("1".to_string()), None];
+        assert!(keyset_predicate(&ratio_then_desk(None), &values, &mut params).is_err());
+    }
+}
@@ -21,10 +21,22 @@ pub enum Dimension {
     UnderlyingSymbol,
     ParentSymbol,
     ExposureType,
+    /// Look-through chain from the traded composite to the constituent, e.g. `PMET → GLD → GOLD`.
+    LookthroughPath,
     Currency,
     Counterparty,
     RiskBucket,
     Scenario,
+    /// Start of the 5-minute interval containing `ts`.
+    Ts5m,
+    /// Start of the hour containing `ts`.
+    TsHour,
+    /// Regional trading session of `ts` (UTC hours): Asia, Europe, Americas or After Hours.
+    TradingSession,
+    /// Monday of the `trade_date` week.
+    TradeWeek,
+    TradeMonth,
+    TradeQuarter,
 }
 
 impl Dimension {
@@ -48,10 +60,62 @@ impl Dimension {
             Dimension::UnderlyingSymbol => "underlying_symbol",
             Dimension::ParentSymbol => "parent_symbol",
             Dimension::ExposureType => "exposure_type",
+            Dimension::LookthroughPath => "lookthrough_path",
             Dimension::Currency => "currency",
             Dimension::Counterparty => "counterparty",
             Dimension::RiskBucket => "risk_bucket",
             Dimension::Scenario => "scenario",
+            Dimension::Ts5m => "ts_5m",
+            Dimension::TsHour => "ts_hour",
+            Dimension::TradingSession => "trading_session",
+            Dimension::TradeWeek => "trade_week",
+            Dimension::TradeMonth => "trade_month",
+            Dimension::TradeQuarter => "trade_quarter",
+        }
+    }
+
+    /// Expression the dimension is computed from. Same as [`Self::to_column`] for
+    /// stored columns; time buckets are derived from `ts` or `trade_date`.
+    pub fn to_expr(&self) -> &'static str {
+        match self {
+            Dimension::Ts5m => "toStartOfFiveMinutes(toDateTime(ts))",
+            Dimension::TsHour => "toStartOfHour(toDateTime(ts))",
+            Dimension::TradingSession => {
+                "multiIf(toHour(ts, 'UTC') < 7, 'Asia', toHour(ts, 'UTC') < 13, 'Europe', \
+                 toHour(ts, 'UTC') < 21, 'Americas', 'After Hours')"
+            }
+            Dimension::TradeWeek => "toMonday(trade_date)",
+            Dimension::TradeMonth => "toStartOfMonth(trade_date)",
+            Dimension::TradeQuarter => "toStartOfQuarter(trade_date)",
+            _ => self.to_column(),
+        }
+    }
+
+    /// `SELECT` list entry: the column, or a derived expression aliased to it.
+    pub fn to_select(&self) -> String {
+        if self.is_derived() {
+            format!("{} AS {}", self.to_expr(), self.to_column())
+        } else {
+            self.to_column().to_string()
+        }
+    }
+
+    /// Whether the dimension is computed rather than stored.
+    pub fn is_derived(&self) -> bool {
+        self.to_expr() != self.to_column()
+    }
+
+    /// ClickHouse type used when binding a value of this dimension as a query parameter.
+    pub fn clickhouse_type(&self) -> &'static str {
+        match self {
+            Dimension::TradeDate
+            | Dimension::TradeWeek
+            | Dimension::TradeMonth
+            | Dimension::TradeQuarter => "Date",
+            Dimension::Ts5m | Dimension::TsHour => "DateTime",
+            Dimension::PortfolioManagerId | Dimension::FundId => "UInt32",
+            Dimension::PortfolioId | Dimension::AccountId => "UInt64",
+            _ => "String",
         }
     }
 
@@ -75,10 +139,17 @@ impl Dimension {
             Dimension::UnderlyingSymbol,
             Dimension::ParentSymbol,
             Dimension::ExposureType,
+            Dimension::LookthroughPath,
             Dimension::Currency,
             Dimension::Counterparty,
             Dimension::RiskBucket,
             Dimension::Scenario,
+            Dimension::Ts5m,
+            Dimension::TsHour,
+            Dimension::TradingSession,
+            Dimension::TradeWeek,
+            Dimension::TradeMonth,
+            Dimension::TradeQuarter,
         ]
     }
 }
@@ -0,0 +1,492 @@
+//! Calculated metrics: a small arithmetic language over `trades_1d` measure columns.
+//!
+//! ```text
+//! expr   := term (('+' | '-') term)*
+//! term   := unary (('*' | '/') unary)*
+//! unary  := '-' unary | atom
+//! atom   := number | column | func '(' [expr (',' expr)*] ')' | '(' expr ')'
+//! ```
+//!
+//! A bare column outside an aggregate is summed, so `(pnl - fees) / notional * 10000`
+//! means `(sum(pnl) - sum(fees)) / sum(notional) * 10000`. Division is always safe:
+//! a zero denominator yields NULL rather than an error or infinity.
+
+use crate::error::ApiError;
+use crate::query::metrics::quantile_level;
+
+const MAX_EXPRESSION_LEN: usize = 512;
+const MAX_NAME_LEN: usize = 64;
+
+/// Numeric `trades_1d` columns a formula (or a range filter) may reference.
+pub const MEASURE_COLUMNS: &[&str] = &[
+    "quantity", "price", "notional", "pnl", "delta", "gamma", "vega", "theta", "rho", "margin",
+    "fees", "slippage", "vol", "rate", "exposure", "weight", "metric_1", "metric_2", "metric_3",
+    "metric_4", "metric_5", "metric_6", "metric_7", "metric_8", "metric_9", "metric_10",
+    "metric_11", "metric_12", "metric_13", "metric_14",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+    StddevPop,
+    /// Quantile at a level in thousandths, e.g. `950` for p95.
+    Quantile(u16),
+}
+
+impl AggFunc {
+    fn name(&self) -> &'static str {
+        match self {
+            AggFunc::Sum => "sum",
+            AggFunc::Avg => "avg",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+            AggFunc::Count => "count",
+            AggFunc::StddevPop => "stddevPop",
+            AggFunc::Quantile(_) => "quantile",
+        }
+    }
+
+    /// Parameter list of a parametric aggregate, e.g. `(0.95)`.
+    fn parameters(&self) -> String {
+        match self {
+            AggFunc::Quantile(level) => format!("({})", quantile_level(*level)),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarFunc {
+    Abs,
+    Sqrt,
+    Ln,
+    Exp,
+    Round,
+    Least,
+    Greatest,
+}
+
+impl ScalarFunc {
+    fn name(&self) -> &'static str {
+        match self {
+            ScalarFunc::Abs => "abs",
+            ScalarFunc::Sqrt => "sqrt",
+            ScalarFunc::Ln => "ln",
+            ScalarFunc::Exp => "exp",
+            ScalarFunc::Round => "round",
+            ScalarFunc::Least => "least",
+            ScalarFunc::Greatest => "greatest",
+        }
+    }
+
+    fn arity(&self) -> std::ops::RangeInclusive<usize> {
+        match self {
+            ScalarFunc::Round => 1..=2,
+            ScalarFunc::Least | ScalarFunc::Greatest => 2..=2,
+            _ => 1..=1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Column(&'static str),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Scalar(ScalarFunc, Vec<Expr>),
+    /// `count()` has no argument.
+    Aggregate(AggFunc, Option<Box<Expr>>),
+}
+
+impl Expr {
+    /// Parses and type-checks `source`, returning an aggregate-level expression.
+    pub fn parse(source: &str) -> Result<Expr, ApiError> {
+        if source.len() > MAX_EXPRESSION_LEN {
+            return Err(invalid(format!(
+                "expression longer than {} characters",
+                MAX_EXPRESSION_LEN
+            )));
+        }
+
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected '{}'", token)));
+        }
+
+        expr.lift(false)
+    }
+
+    /// Renders the expression as ClickHouse SQL, restricting every aggregate to
+    /// `condition` when given.
+    pub fn to_sql(&self, condition: Option<&str>) -> String {
+        match self {
+            Expr::Number(n) => format!("{}", n),
+            Expr::Column(c) => c.to_string(),
+            Expr::Neg(e) => format!("-({})", e.to_sql(condition)),
+            Expr::Binary(BinOp::Div, l, r) => format!(
+                "({} / nullIf({}, 0))",
+                l.to_sql(condition),
+                r.to_sql(condition)
+            ),
+            Expr::Binary(op, l, r) => {
+                let op = match op {
+                    BinOp::Add => "+",
+                    BinOp::Sub => "-",
+                    BinOp::Mul => "*",
+                    BinOp::Div => unreachable!(),
+                };
+                format!("({} {} {})", l.to_sql(condition), op, r.to_sql(condition))
+            }
+            Expr::Scalar(f, args) => format!(
+                "{}({})",
+                f.name(),
+                args.iter()
+                    .map(|a| a.to_sql(condition))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Expr::Aggregate(f, arg) => {
+                let params = f.parameters();
+                match (arg, condition) {
+                    (None, None) => format!("{}{}()", f.name(), params),
+                    (None, Some(c)) => format!("{}If{}({})", f.name(), params, c),
+                    (Some(a), None) => format!("{}{}({})", f.name(), params, a.to_sql(None)),
+                    (Some(a), Some(c)) => {
+                        format!("{}If{}({}, {})", f.name(), params, a.to_sql(None), c)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Wraps bare columns outside aggregates in `sum()` and rejects nested aggregates.
+    fn lift(self, inside_aggregate: bool) -> Result<Expr, ApiError> {
+        Ok(match self {
+            Expr::Column(c) if !inside_aggregate => {
+                Expr::Aggregate(AggFunc::Sum, Some(Box::new(Expr::Column(c))))
+            }
+            Expr::Aggregate(f, arg) => {
+                if inside_aggregate {
+                    return Err(invalid(format!("nested aggregate '{}'", f.name())));
+                }
+                Expr::Aggregate(f, arg.map(|a| a.lift(true)).transpose()?.map(Box::new))
+            }
+            Expr::Neg(e) => Expr::Neg(Box::new(e.lift(inside_aggregate)?)),
+            Expr::Binary(op, l, r) => Expr::Binary(
+                op,
+                Box::new(l.lift(inside_aggregate)?),
+                Box::new(r.lift(inside_aggregate)?),
+            ),
+            Expr::Scalar(f, args) => Expr::Scalar(
+                f,
+                args.into_iter()
+                    .map(|a| a.lift(inside_aggregate))
+                    .collect::<Result<_, _>>()?,
+            ),
+            other => other,
+        })
+    }
+}
+
+/// Checks a calculated metric name is a plain identifier that can be used as an alias.
+pub fn validate_name(name: &str) -> Result<(), ApiError> {
+    let mut chars = name.chars();
+    let valid = name.len() <= MAX_NAME_LEN
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::QueryValidation(format!(
+            "Invalid calculated metric name: '{}'. Use lowercase letters, digits and underscores",
+            name
+        )))
+    }
+}
+
+fn invalid(msg: String) -> ApiError {
+    ApiError::QueryValidation(format!("Invalid calculated metric expression: {}", msg))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ApiError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text
+                .parse()
+                .map_err(|_| invalid(format!("bad number '{}'", text)))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err(invalid(format!("unexpected character '{}'", c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), ApiError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(invalid(format!("expected '{}'", op)))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ApiError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinOp::Add
+            } else if self.eat('-') {
+                BinOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, ApiError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinOp::Mul
+            } else if self.eat('/') {
+                BinOp::Div
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ApiError> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, ApiError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::Op('(')) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                call(&name, args)
+            }
+            Some(Token::Ident(name)) => MEASURE_COLUMNS
+                .iter()
+                .find(|c| **c == name)
+                .map(|c| Expr::Column(c))
+                .ok_or_else(|| {
+                    invalid(format!("unknown column '{}'. Allowed: {:?}", name, MEASURE_COLUMNS))
+                }),
+            Some(token) => Err(invalid(format!("unexpected '{}'", token))),
+            None => Err(invalid("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+fn call(name: &str, mut args: Vec<Expr>) -> Result<Expr, ApiError> {
+    // quantile(level, expr): the level must be a literal strictly between 0 and 1,
+    // in whole thousandths so it is used exactly as written
+    if name == "quantile" {
+        return match args.as_slice() {
+            [Expr::Number(level), _] => {
+                let permille = level * 1000.0;
+                if !(1.0..=999.0).contains(&permille) || (permille - permille.round()).abs() > 1e-6 {
+                    return Err(invalid(format!(
+                        "quantile() level must be between 0.001 and 0.999 in steps of 0.001, got {}",
+                        level
+                    )));
+                }
+                let level = permille.round() as u16;
+                Ok(Expr::Aggregate(AggFunc::Quantile(level), args.pop().map(Box::new)))
+            }
+            _ => Err(invalid(
+                "quantile() takes a level between 0 and 1 and an expression".to_string(),
+            )),
+        };
+    }
+
+    let aggregate = match name {
+        "sum" => Some(AggFunc::Sum),
+        "avg" => Some(AggFunc::Avg),
+        "min" => Some(AggFunc::Min),
+        "max" => Some(AggFunc::Max),
+        "count" => Some(AggFunc::Count),
+        "stddev" => Some(AggFunc::StddevPop),
+        "median" => Some(AggFunc::Quantile(500)),
+        _ => None,
+    };
+    if let Some(f) = aggregate {
+        let expected = if f == AggFunc::Count { 0 } else { 1 };
+        if args.len() != expected {
+            return Err(invalid(format!("{}() takes {} argument(s)", name, expected)));
+        }
+        return Ok(Expr::Aggregate(f, args.pop().map(Box::new)));
+    }
+
+    let scalar = match name {
+        "abs" => ScalarFunc::Abs,
+        "sqrt" => ScalarFunc::Sqrt,
+        "ln" => ScalarFunc::Ln,
+        "exp" => ScalarFunc::Exp,
+        "round" => ScalarFunc::Round,
+        "least" => ScalarFunc::Least,
+        "greatest" => ScalarFunc::Greatest,
+        _ => return Err(invalid(format!("unknown function '{}'", name))),
+    };
+    if !scalar.arity().contains(&args.len()) {
+        return Err(invalid(format!("wrong number of arguments to {}()", name)));
+    }
+    Ok(Expr::Scalar(scalar, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_columns_are_summed() {
+        let expr = Expr::parse("(pnl - fees) / notional * 10000").unwrap();
+        assert_eq!(
+            expr.to_sql(None),
+            "(((sum(pnl) - sum(fees)) / nullIf(sum(notional), 0)) * 10000)"
+        );
+    }
+
+    #[test]
+    fn test_explicit_aggregates_and_conditions() {
+        let expr = Expr::parse("sum(abs(metric_3) * weight) / count()").unwrap();
+        assert_eq!(
+            expr.to_sql(Some("desk = 'FX'")),
+            "(sumIf((abs(metric_3) * weight), desk = 'FX') / nullIf(countIf(desk = 'FX'), 0))"
+        );
+    }
+
+    #[test]
+    fn test_distribution_aggregates() {
+        let expr = Expr::parse("quantile(0.99, slippage) - median(slippage)").unwrap();
+        assert_eq!(
+            expr.to_sql(None),
+            "(quantile(0.99)(slippage) - quantile(0.5)(slippage))"
+        );
+        assert_eq!(
+            Expr::parse("stddev(pnl)").unwrap().to_sql(Some("desk = 'FX'")),
+            "stddevPopIf(pnl, desk = 'FX')"
+        );
+        assert!(Expr::parse("quantile(1.5, slippage)").is_err());
+        // Levels are never silently rounded to another thousandth, nor to 0 or 1
+        assert!(Expr::parse("quantile(0.9995, slippage)").is_err());
+        assert!(Expr::parse("quantile(0.0004, slippage)").is_err());
+        assert!(Expr::parse("quantile(0.9999, slippage)").is_err());
+        assert!(Expr::parse("quantile(0.975, slippage)").is_ok());
+        assert!(Expr::parse("quantile(pnl, slippage)").is_err());
+    }
+
+    #[test]
+    fn test_rejects_unsafe_input() {
+        assert!(Expr::parse("pnl; DROP TABLE trades_1d").is_err());
+        assert!(Expr::parse("symbol").is_err());
+        assert!(Expr::parse("sleep(3)").is_err());
+        assert!(Expr::parse("sum(sum(pnl))").is_err());
+        assert!(Expr::parse("pnl +").is_err());
+        assert!(Expr::parse("least(pnl)").is_err());
+        assert!(validate_name("net_bps").is_ok());
+        assert!(validate_name("x AS y").is_err());
+    }
+}
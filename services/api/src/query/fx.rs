@@ -0,0 +1,89 @@
+//! Conversion of monetary columns into a reporting currency.
+//!
+//! `pivot.fx_rates` holds one rate per day for each currency against `USD`; other
+//! pairs are crossed through it. Rows are converted at their `trade_date` rate
+//! before aggregation. A missing rate fails the query rather than silently
+//! dropping or mis-scaling rows.
+
+use crate::error::ApiError;
+use crate::query::params::QueryParams;
+
+/// Currency every rate in `pivot.fx_rates` is quoted against.
+pub const BASE_CURRENCY: &str = "USD";
+
+/// Columns holding amounts in the trade currency.
+pub const MONETARY_COLUMNS: &[&str] = &["price", "notional", "pnl", "margin", "fees", "exposure"];
+
+/// Checks `currency` is an ISO 4217 style code, e.g. `EUR`.
+pub fn validate_currency(currency: &str) -> Result<(), ApiError> {
+    if currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase()) {
+        Ok(())
+    } else {
+        Err(ApiError::QueryValidation(format!(
+            "Invalid reporting_currency: '{}'. Expected a 3-letter code such as EUR",
+            currency
+        )))
+    }
+}
+
+/// Wraps `table` (a table name or parenthesised subquery with the `trades_1d`
+/// columns) so its monetary columns are in `currency`.
+pub fn converted_source(table: &str, currency: &str, params: &mut QueryParams) -> String {
+    let target = params.bind("String", currency);
+    let mut converted: Vec<String> = MONETARY_COLUMNS
+        .iter()
+        .map(|c| format!("{} * fx_rate AS {}", c, c))
+        .collect();
+    converted.push(format!("{} AS currency", target));
+
+    // A missing rate joins as 0, so the rate is only usable once checked. The check
+    // is folded into `fx_rate` itself; a separate column would be pruned unread.
+    format!(
+        "(SELECT * EXCEPT (fx_raw, fx_rate) REPLACE ({converted}) FROM (\
+         SELECT t.*, \
+         if(t.currency = {target}, 1, \
+         if(t.currency = '{base}', 1, fx_src.rate) / if({target} = '{base}', 1, fx_dst.rate)) AS fx_raw, \
+         fx_raw * (1 + throwIf(NOT isFinite(fx_raw) OR fx_raw <= 0, \
+         'Missing FX rate for reporting currency conversion')) AS fx_rate \
+         FROM {table} AS t \
+         LEFT JOIN ({rates}) AS fx_src ON fx_src.rate_date = t.trade_date AND fx_src.base_currency = t.currency \
+         LEFT JOIN ({rates} AND base_currency = {target}) AS fx_dst ON fx_dst.rate_date = t.trade_date\
+         ))",
+        converted = converted.join(", "),
+        target = target,
+        base = BASE_CURRENCY,
+        table = table,
+        rates = format!(
+            "SELECT rate_date, base_currency, rate FROM pivot.fx_rates FINAL WHERE quote_currency = '{}'",
+            BASE_CURRENCY
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converted_source() {
+        let mut params = QueryParams::new();
+        let sql = converted_source("pivot.trades_1d", "EUR", &mut params);
+
+        assert!(sql.starts_with(
+            "(SELECT * EXCEPT (fx_raw, fx_rate) REPLACE (price * fx_rate AS price, notional * fx_rate AS notional"
+        ));
+        assert!(sql.contains("exposure * fx_rate AS exposure, {p0:String} AS currency)"));
+        assert!(sql.contains("fx_raw * (1 + throwIf(NOT isFinite(fx_raw) OR fx_raw <= 0"));
+        assert!(sql.contains("FROM pivot.trades_1d AS t LEFT JOIN"));
+        assert!(sql.contains("WHERE quote_currency = 'USD' AND base_currency = {p0:String}) AS fx_dst"));
+        assert_eq!(params.get("p0"), Some("EUR"));
+    }
+
+    #[test]
+    fn test_invalid_currency_rejected() {
+        assert!(validate_currency("GBP").is_ok());
+        assert!(validate_currency("usd").is_err());
+        assert!(validate_currency("EURO").is_err());
+        assert!(validate_currency("E'R").is_err());
+    }
+}
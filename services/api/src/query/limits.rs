@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::request::{
+    Condition, ExposureType, LimitKind, PivotFilters, PivotRequest, Predicate, RiskLimitSpec,
+};
+use crate::models::response::{LimitUtilisation, RiskLimit};
+use crate::query::params::{BoundQuery, QueryParams};
+use crate::query::predicate::compile_predicate;
+use crate::query::PivotQueryBuilder;
+
+/// Most groups a single limit is evaluated over.
+const MAX_LIMIT_GROUPS: u32 = 10_000;
+
+/// Alias of the concentration value column.
+const CONCENTRATION_ALIAS: &str = "max_share_pct";
+
+/// Alias of the largest name in a concentration group. Distinct from
+/// `underlying_symbol`, which ClickHouse would otherwise substitute inside `argMax`.
+const SYMBOL_ALIAS: &str = "top_symbol";
+
+/// Checks a limit definition before it is stored.
+pub fn validate_limit(spec: &RiskLimitSpec) -> Result<(), ApiError> {
+    if spec.name.trim().is_empty() {
+        return Err(ApiError::QueryValidation("Limit name must not be empty".to_string()));
+    }
+    if !(spec.max_value.is_finite() && spec.max_value > 0.0) {
+        return Err(ApiError::QueryValidation(
+            "max_value must be a positive number".to_string(),
+        ));
+    }
+    if spec.kind == LimitKind::Concentration && spec.max_value > 100.0 {
+        return Err(ApiError::QueryValidation(
+            "Concentration limits are a percent of gross and cannot exceed 100".to_string(),
+        ));
+    }
+    if spec.dimension.is_derived() {
+        return Err(ApiError::QueryValidation(format!(
+            "Limits apply per stored dimension, not '{}'",
+            spec.dimension.to_column()
+        )));
+    }
+    if let Some(predicate) = scope_predicate(spec) {
+        compile_predicate(&predicate, &mut QueryParams::new())?;
+    }
+    Ok(())
+}
+
+/// Aggregates one limit is checked against on `trade_date`, one row per group.
+///
+/// Metric limits go through the pivot builder, so they aggregate exactly as the
+/// same pivot would, over traded positions only: exploded constituent rows would
+/// count each ETF/ETC trade twice.
+pub fn evaluation_query(limit: &RiskLimit, trade_date: &str) -> Result<BoundQuery, ApiError> {
+    let spec = &limit.spec;
+    let filters = PivotFilters {
+        trade_date: Some(trade_date.to_string()),
+        predicates: scope_predicate(spec).into_iter().collect(),
+        ..Default::default()
+    };
+
+    match spec.kind {
+        LimitKind::Metric { metric, .. } => {
+            let request = PivotRequest {
+                dimensions: vec![spec.dimension],
+                metrics: vec![metric],
+                filters: PivotFilters {
+                    exposure_type: Some(vec![
+                        ExposureType::Direct,
+                        ExposureType::Etf,
+                        ExposureType::Etc,
+                    ]),
+                    ..filters
+                },
+                limit: MAX_LIMIT_GROUPS,
+                ..Default::default()
+            };
+            Ok(PivotQueryBuilder::from_request(&request)?.build())
+        }
+        LimitKind::Concentration => {
+            let mut params = QueryParams::new();
+            let where_clauses = filters
+                .to_predicates()
+                .iter()
+                .map(|p| compile_predicate(p, &mut params))
+                .collect::<Result<Vec<_>, _>>()?;
+            let group = spec.dimension.to_column();
+
+            let sql = format!(
+                "SELECT {group}, argMax(underlying_symbol, share) AS {sym}, max(share) AS {value} FROM (\
+                 SELECT {group}, underlying_symbol, abs(sum(notional)) \
+                 / nullIf(sum(abs(sum(notional))) OVER (PARTITION BY {group}), 0) * 100 AS share \
+                 FROM pivot.trades_1d \
+                 WHERE {where} AND exposure_type IN ('Direct', 'Constituent') \
+                 GROUP BY {group}, underlying_symbol\
+                 ) GROUP BY {group} ORDER BY {value} DESC LIMIT {limit}",
+                group = group,
+                sym = SYMBOL_ALIAS,
+                value = CONCENTRATION_ALIAS,
+                where = where_clauses.join(" AND "),
+                limit = MAX_LIMIT_GROUPS
+            );
+            Ok(BoundQuery::new(sql, params))
+        }
+    }
+}
+
+/// Utilisation of `limit` in each group returned by its [`evaluation_query`].
+pub fn evaluate(limit: &RiskLimit, rows: Vec<HashMap<String, Value>>) -> Vec<LimitUtilisation> {
+    let spec = &limit.spec;
+    let column = spec.dimension.to_column();
+    let (value_alias, absolute) = match spec.kind {
+        LimitKind::Metric { metric, absolute } => (metric.alias(), absolute),
+        LimitKind::Concentration => (CONCENTRATION_ALIAS, false),
+    };
+
+    rows.into_iter()
+        .filter_map(|mut row| {
+            let raw = row.get(value_alias).and_then(|v| {
+                v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            })?;
+            let value = if absolute { raw.abs() } else { raw };
+            let underlying_symbol = match spec.kind {
+                LimitKind::Concentration => row
+                    .remove(SYMBOL_ALIAS)
+                    .and_then(|v| v.as_str().map(str::to_string)),
+                LimitKind::Metric { .. } => None,
+            };
+
+            Some(LimitUtilisation {
+                limit_id: limit.limit_id.clone(),
+                name: spec.name.clone(),
+                dimension: column.to_string(),
+                group: row.remove(column).unwrap_or(Value::Null),
+                underlying_symbol,
+                value,
+                max_value: spec.max_value,
+                utilisation_pct: value / spec.max_value * 100.0,
+                breached: value > spec.max_value,
+            })
+        })
+        .collect()
+}
+
+fn scope_predicate(spec: &RiskLimitSpec) -> Option<Predicate> {
+    spec.scope_value.as_ref().map(|value| {
+        Predicate::new(Condition::Eq {
+            dimension: spec.dimension,
+            value: Value::String(value.clone()),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{Dimension, Metric};
+    use serde_json::json;
+
+    fn limit(kind: LimitKind, dimension: Dimension, max_value: f64) -> RiskLimit {
+        RiskLimit {
+            limit_id: "l-1".to_string(),
+            spec: RiskLimitSpec {
+                name: "test".to_string(),
+                kind,
+                dimension,
+                scope_value: None,
+                max_value,
+            },
+        }
+    }
+
+    #[test]
+    fn test_limit_spec_from_json() {
+        let spec: RiskLimitSpec = serde_json::from_value(json!({
+            "name": "Book delta",
+            "kind": "metric",
+            "metric": "delta",
+            "absolute": true,
+            "dimension": "book",
+            "max_value": 500
+        }))
+        .unwrap();
+
+        assert_eq!(spec.kind, LimitKind::Metric { metric: Metric::Delta, absolute: true });
+        assert!(validate_limit(&spec).is_ok());
+    }
+
+    #[test]
+    fn test_metric_limit_uses_pivot_query() {
+        let mut desk = limit(
+            LimitKind::Metric { metric: Metric::GrossNotional, absolute: false },
+            Dimension::Desk,
+            1e9,
+        );
+        desk.spec.scope_value = Some("FX".to_string());
+        let query = evaluation_query(&desk, "2024-01-15").unwrap();
+
+        assert!(query.sql.starts_with("SELECT desk, sum(abs(notional)) AS gross_notional"));
+        // Exploded constituent rows are left out so ETF/ETC trades count once
+        assert!(query.sql.contains(
+            "WHERE trade_date = {p0:Date} \
+             AND exposure_type IN ({p1:String}, {p2:String}, {p3:String}) \
+             AND desk = {p4:String} GROUP BY desk"
+        ));
+        let types: Vec<_> = ["p1", "p2", "p3"].iter().map(|p| query.params.get(p)).collect();
+        assert_eq!(types, vec![Some("Direct"), Some("ETF"), Some("ETC")]);
+        assert_eq!(query.params.get("p4"), Some("FX"));
+    }
+
+    #[test]
+    fn test_concentration_limit_query() {
+        let fund = limit(LimitKind::Concentration, Dimension::FundId, 10.0);
+        let query = evaluation_query(&fund, "2024-01-15").unwrap();
+
+        assert!(query.sql.starts_with(
+            "SELECT fund_id, argMax(underlying_symbol, share) AS top_symbol"
+        ));
+        assert!(query.sql.contains("OVER (PARTITION BY fund_id)"));
+        assert!(query.sql.contains("WHERE trade_date = {p0:Date} AND exposure_type"));
+    }
+
+    #[test]
+    fn test_evaluate_utilisation() {
+        let book = limit(
+            LimitKind::Metric { metric: Metric::Delta, absolute: true },
+            Dimension::Book,
+            200.0,
+        );
+        let rows = vec![
+            HashMap::from([
+                ("book".to_string(), json!("Alpha")),
+                ("total_delta".to_string(), json!(-300.0)),
+            ]),
+            HashMap::from([
+                ("book".to_string(), json!("Beta")),
+                ("total_delta".to_string(), json!(50.0)),
+            ]),
+        ];
+
+        let results = evaluate(&book, rows);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].group, json!("Alpha"));
+        assert_eq!(results[0].value, 300.0);
+        assert_eq!(results[0].utilisation_pct, 150.0);
+        assert!(results[0].breached);
+        assert_eq!(results[1].utilisation_pct, 25.0);
+        assert!(!results[1].breached);
+    }
+
+    #[test]
+    fn test_evaluate_concentration_reports_top_symbol() {
+        let fund = limit(LimitKind::Concentration, Dimension::FundId, 10.0);
+        let rows = vec![HashMap::from([
+            ("fund_id".to_string(), json!(7)),
+            ("top_symbol".to_string(), json!("AAPL")),
+            ("max_share_pct".to_string(), json!(12.5)),
+        ])];
+
+        let results = evaluate(&fund, rows);
+        assert_eq!(results[0].underlying_symbol.as_deref(), Some("AAPL"));
+        assert!(results[0].breached);
+    }
+
+    #[test]
+    fn test_invalid_limits_rejected() {
+        let mut spec = limit(LimitKind::Concentration, Dimension::FundId, 120.0).spec;
+        assert!(validate_limit(&spec).is_err());
+
+        spec.max_value = 10.0;
+        spec.scope_value = Some("not-a-fund".to_string());
+        assert!(validate_limit(&spec).is_err());
+
+        spec.scope_value = None;
+        spec.dimension = Dimension::TradeMonth;
+        assert!(validate_limit(&spec).is_err());
+
+        spec.dimension = Dimension::FundId;
+        spec.name = " ".to_string();
+        assert!(validate_limit(&spec).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Query-time look-through of ETF/ETC trades into their constituents.
+//!
+//! Each composite trade is split into one leg per constituent in effect on its
+//! `trade_date` (`effective_date <= trade_date < expiry_date`), with additive
+//! measures, `slippage` and the custom `metric_N` included, scaled by the
+//! constituent weight. Constituents that are themselves composites are resolved
+//! recursively, multiplying weights along the chain. Whatever the weights leave
+//! unexplained stays on a residual leg under the original exposure type, so sums
+//! match the top-level view; trades are counted by `trade_id`, while averages and
+//! quantiles are taken over legs. Trades without
+//! constituents pass through unchanged, and `Constituent` rows already
+//! materialised by `pivot-data-gen --explode-constituents` are skipped so
+//! nothing is counted twice.
+
+use std::sync::LazyLock;
+
+use crate::query::constituents::effective_on;
+
+/// Deepest nesting of composites resolved; chains still ending at a composite
+/// after this many levels are treated as leaves.
+pub const MAX_LOOK_THROUGH_DEPTH: usize = 4;
+
+/// Number of `metric_N` columns, scaled like the other additive measures.
+const CUSTOM_METRICS: usize = 14;
+
+/// Separator between symbols in `lookthrough_path`.
+pub const PATH_SEPARATOR: &str = " → ";
+
+/// Subquery with the columns of `pivot.trades_1d`, to read from in place of the table.
+///
+/// Legs are `(underlying_symbol, weight, is_constituent, lookthrough_path)` tuples;
+/// constituent legs keep the traded `symbol` and take it as their `parent_symbol`.
+pub static LOOK_THROUGH_SOURCE: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "(SELECT * EXCEPT (effective, legs, leg) REPLACE (\
+         leg.1 AS underlying_symbol, \
+         if(leg.3 = 1, symbol, parent_symbol) AS parent_symbol, \
+         if(leg.3 = 1, 'Constituent', exposure_type) AS exposure_type, \
+         leg.4 AS lookthrough_path, \
+         quantity * leg.2 AS quantity, notional * leg.2 AS notional, pnl * leg.2 AS pnl, \
+         delta * leg.2 AS delta, gamma * leg.2 AS gamma, vega * leg.2 AS vega, \
+         theta * leg.2 AS theta, rho * leg.2 AS rho, margin * leg.2 AS margin, \
+         fees * leg.2 AS fees, exposure * leg.2 AS exposure, weight * leg.2 AS weight, \
+         slippage * leg.2 AS slippage, {}) \
+         FROM ({} \
+         SELECT t.*, \
+         arrayFilter(m -> {}, c.lt_members) AS effective, \
+         arrayFilter(l -> abs(l.2) > 1e-9, arrayConcat(\
+         arrayMap(m -> (m.1, m.2, toUInt8(1), m.5), effective), \
+         [(t.underlying_symbol, 1 - arraySum(m -> m.2, effective), toUInt8(0), t.lookthrough_path)])) AS legs \
+         FROM pivot.trades_1d AS t \
+         LEFT JOIN (\
+         SELECT root AS lt_parent, \
+         groupArray((leaf, w, lt_eff, lt_exp, arrayStringConcat(path, '{}'))) AS lt_members \
+         FROM ({}) GROUP BY root\
+         ) AS c ON c.lt_parent = t.symbol \
+         WHERE t.exposure_type != 'Constituent'\
+         ) ARRAY JOIN legs AS leg)",
+        (1..=CUSTOM_METRICS)
+            .map(|i| format!("metric_{i} * leg.2 AS metric_{i}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        chain_levels(),
+        effective_on("m.3", "m.4", "t.trade_date"),
+        PATH_SEPARATOR,
+        leaf_chains()
+    )
+});
+
+/// `WITH` clause defining `lt_1` .. `lt_N`, the constituent chains `N` levels deep.
+///
+/// Each level extends the previous one through the baskets of its leaves, multiplying
+/// weights and intersecting the effective periods. A constituent already on the path
+/// is never followed again, so cyclic baskets terminate; the weight of the cycling
+/// branch is left on the residual leg, as `pivot-data-gen` leaves it on the composite.
+fn chain_levels() -> String {
+    let mut levels = vec![
+        "lt_1 AS (SELECT parent_symbol AS root, constituent_symbol AS leaf, weight AS w, \
+         effective_date AS lt_eff, expiry_date AS lt_exp, [parent_symbol, constituent_symbol] AS path \
+         FROM pivot.constituents FINAL)"
+            .to_string(),
+    ];
+    for depth in 2..=MAX_LOOK_THROUGH_DEPTH {
+        levels.push(format!(
+            "lt_{} AS (SELECT l.root AS root, c.constituent_symbol AS leaf, l.w * c.weight AS w, \
+             greatest(l.lt_eff, c.effective_date) AS lt_eff, least(l.lt_exp, c.expiry_date) AS lt_exp, \
+             arrayPushBack(l.path, c.constituent_symbol) AS path \
+             FROM lt_{} AS l INNER JOIN (SELECT * FROM pivot.constituents FINAL) AS c ON c.parent_symbol = l.leaf \
+             WHERE NOT has(l.path, c.constituent_symbol) AND lt_eff < lt_exp)",
+            depth,
+            depth - 1
+        ));
+    }
+    format!("WITH {}", levels.join(", "))
+}
+
+/// Chains ending at a non-composite, from every level; the deepest level is kept whole.
+fn leaf_chains() -> String {
+    (1..=MAX_LOOK_THROUGH_DEPTH)
+        .map(|depth| {
+            if depth == MAX_LOOK_THROUGH_DEPTH {
+                format!("SELECT * FROM lt_{}", depth)
+            } else {
+                format!(
+                    "SELECT * FROM lt_{} WHERE leaf NOT IN (SELECT parent_symbol FROM pivot.constituents)",
+                    depth
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_look_through_source() {
+        for column in ["quantity", "notional", "pnl", "delta", "exposure", "slippage", "metric_14"] {
+            assert!(LOOK_THROUGH_SOURCE.contains(&format!("{} * leg.2 AS {}", column, column)));
+        }
+        assert!(LOOK_THROUGH_SOURCE.contains("m.3 <= t.trade_date AND t.trade_date < m.4"));
+        assert!(LOOK_THROUGH_SOURCE.contains("WHERE t.exposure_type != 'Constituent'"));
+    }
+
+    #[test]
+    fn test_nested_chains() {
+        let levels = chain_levels();
+        assert!(levels.starts_with("WITH lt_1 AS (SELECT parent_symbol AS root"));
+        assert!(levels.contains(&format!(
+            "lt_{} AS (SELECT l.root AS root, c.constituent_symbol AS leaf, l.w * c.weight AS w",
+            MAX_LOOK_THROUGH_DEPTH
+        )));
+        assert!(levels.contains("WHERE NOT has(l.path, c.constituent_symbol)"));
+
+        let leaves = leaf_chains();
+        assert_eq!(leaves.matches(" UNION ALL ").count(), MAX_LOOK_THROUGH_DEPTH - 1);
+        assert!(leaves.ends_with(&format!("SELECT * FROM lt_{}", MAX_LOOK_THROUGH_DEPTH)));
+    }
+}
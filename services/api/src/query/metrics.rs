@@ -17,28 +17,223 @@ pub enum Metric {
     Slippage,
     Exposure,
     TradeCount,
+    /// P&L per unit of notional, in basis points.
+    PnlBps,
+    /// Fees as a percentage of notional.
+    FeesPct,
+    /// Quantity-weighted average price, `sum(price * quantity) / sum(quantity)`.
+    Vwap,
+    /// Volatility weighted by absolute notional.
+    WeightedVol,
+    GrossNotional,
+    NetNotional,
+    LongNotional,
+    ShortNotional,
+    MedianSlippage,
+    P95Slippage,
+    /// Population standard deviation of trade P&L.
+    PnlStddev,
+    MinPrice,
+    MaxPrice,
+    DistinctOrders,
+    DistinctCounterparties,
+    DistinctAccounts,
+}
+
+/// Columns pre-aggregated with `sumState` in `trades_1d_rollup`.
+const ROLLUP_SUM_COLUMNS: &[&str] = &["quantity", "notional", "pnl"];
+
+/// A single ClickHouse aggregate over `trades_1d` columns.
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum(&'static str),
+    /// `sumIf(expr, predicate)`
+    SumWhere(&'static str, &'static str),
+    Avg(&'static str),
+    Min(&'static str),
+    Max(&'static str),
+    StddevPop(&'static str),
+    /// `quantile(level)(expr)`, with `level` in thousandths.
+    Quantile(&'static str, u16),
+    /// `uniqExact(expr)`
+    Distinct(&'static str),
+    Count,
+}
+
+impl Aggregate {
+    /// Renders the aggregate, restricted to `condition` if given.
+    ///
+    /// With `rollup` set, only sums over `trades_1d_rollup` state columns can be
+    /// rendered; anything else yields `None`.
+    fn render(&self, condition: Option<&str>, rollup: bool) -> Option<String> {
+        if rollup {
+            return match self {
+                Aggregate::Sum(col) if ROLLUP_SUM_COLUMNS.contains(col) => Some(match condition {
+                    Some(c) => format!("sumMergeIf({}_state, {})", col, c),
+                    None => format!("sumMerge({}_state)", col),
+                }),
+                _ => None,
+            };
+        }
+
+        Some(match (self, condition) {
+            (Aggregate::Sum(expr), None) => format!("sum({})", expr),
+            (Aggregate::Sum(expr), Some(c)) => format!("sumIf({}, {})", expr, c),
+            (Aggregate::SumWhere(expr, p), None) => format!("sumIf({}, {})", expr, p),
+            (Aggregate::SumWhere(expr, p), Some(c)) => {
+                format!("sumIf({}, ({}) AND ({}))", expr, p, c)
+            }
+            (Aggregate::Avg(expr), None) => format!("avg({})", expr),
+            (Aggregate::Avg(expr), Some(c)) => format!("avgIf({}, {})", expr, c),
+            (Aggregate::Min(expr), None) => format!("min({})", expr),
+            (Aggregate::Min(expr), Some(c)) => format!("minIf({}, {})", expr, c),
+            (Aggregate::Max(expr), None) => format!("max({})", expr),
+            (Aggregate::Max(expr), Some(c)) => format!("maxIf({}, {})", expr, c),
+            (Aggregate::StddevPop(expr), None) => format!("stddevPop({})", expr),
+            (Aggregate::StddevPop(expr), Some(c)) => format!("stddevPopIf({}, {})", expr, c),
+            (Aggregate::Quantile(expr, level), None) => {
+                format!("quantile({})({})", quantile_level(*level), expr)
+            }
+            (Aggregate::Quantile(expr, level), Some(c)) => {
+                format!("quantileIf({})({}, {})", quantile_level(*level), expr, c)
+            }
+            (Aggregate::Distinct(expr), None) => format!("uniqExact({})", expr),
+            (Aggregate::Distinct(expr), Some(c)) => format!("uniqExactIf({}, {})", expr, c),
+            (Aggregate::Count, None) => "count()".to_string(),
+            (Aggregate::Count, Some(c)) => format!("countIf({})", c),
+        })
+    }
+}
+
+/// Renders a level in thousandths as a decimal, e.g. `950` as `0.95`.
+pub(crate) fn quantile_level(permille: u16) -> String {
+    let level = format!("{:.3}", f64::from(permille) / 1000.0);
+    level.trim_end_matches('0').to_string()
+}
+
+/// How a metric is computed from aggregates.
+#[derive(Debug, Clone, Copy)]
+enum Formula {
+    Single(Aggregate),
+    /// `numerator / denominator * scale`, NULL when the denominator is zero.
+    ///
+    /// Both sides are aggregated per group, so subtotal rows get the ratio of their
+    /// sums rather than a sum of ratios.
+    Ratio {
+        numerator: Aggregate,
+        denominator: Aggregate,
+        scale: u32,
+    },
+}
+
+impl Formula {
+    fn render(&self, condition: Option<&str>, rollup: bool) -> Option<String> {
+        match self {
+            Formula::Single(agg) => agg.render(condition, rollup),
+            Formula::Ratio { numerator, denominator, scale } => {
+                let ratio = format!(
+                    "{} / nullIf({}, 0)",
+                    numerator.render(condition, rollup)?,
+                    denominator.render(condition, rollup)?
+                );
+                Some(if *scale == 1 { ratio } else { format!("{} * {}", ratio, scale) })
+            }
+        }
+    }
 }
 
 impl Metric {
-    pub fn to_aggregation(&self) -> &'static str {
+    fn formula(&self) -> Formula {
+        use Aggregate::*;
+
         match self {
-            Metric::Quantity => "sum(quantity)",
-            Metric::Notional => "sum(notional)",
-            Metric::Pnl => "sum(pnl)",
-            Metric::Price => "avg(price)",
-            Metric::Delta => "sum(delta)",
-            Metric::Gamma => "sum(gamma)",
-            Metric::Vega => "sum(vega)",
-            Metric::Theta => "sum(theta)",
-            Metric::Rho => "sum(rho)",
-            Metric::Margin => "sum(margin)",
-            Metric::Fees => "sum(fees)",
-            Metric::Slippage => "sum(slippage)",
-            Metric::Exposure => "sum(exposure)",
-            Metric::TradeCount => "count()",
+            Metric::Quantity => Formula::Single(Sum("quantity")),
+            Metric::Notional | Metric::NetNotional => Formula::Single(Sum("notional")),
+            Metric::Pnl => Formula::Single(Sum("pnl")),
+            Metric::Price => Formula::Single(Avg("price")),
+            Metric::Delta => Formula::Single(Sum("delta")),
+            Metric::Gamma => Formula::Single(Sum("gamma")),
+            Metric::Vega => Formula::Single(Sum("vega")),
+            Metric::Theta => Formula::Single(Sum("theta")),
+            Metric::Rho => Formula::Single(Sum("rho")),
+            Metric::Margin => Formula::Single(Sum("margin")),
+            Metric::Fees => Formula::Single(Sum("fees")),
+            Metric::Slippage => Formula::Single(Sum("slippage")),
+            Metric::Exposure => Formula::Single(Sum("exposure")),
+            Metric::TradeCount => Formula::Single(Count),
+            Metric::PnlBps => Formula::Ratio {
+                numerator: Sum("pnl"),
+                denominator: Sum("notional"),
+                scale: 10000,
+            },
+            Metric::FeesPct => Formula::Ratio {
+                numerator: Sum("fees"),
+                denominator: Sum("notional"),
+                scale: 100,
+            },
+            Metric::Vwap => Formula::Ratio {
+                numerator: Sum("price * quantity"),
+                denominator: Sum("quantity"),
+                scale: 1,
+            },
+            Metric::WeightedVol => Formula::Ratio {
+                numerator: Sum("vol * abs(notional)"),
+                denominator: Sum("abs(notional)"),
+                scale: 1,
+            },
+            Metric::GrossNotional => Formula::Single(Sum("abs(notional)")),
+            Metric::LongNotional => Formula::Single(SumWhere("notional", "notional > 0")),
+            Metric::ShortNotional => Formula::Single(SumWhere("notional", "notional < 0")),
+            Metric::MedianSlippage => Formula::Single(Quantile("slippage", 500)),
+            Metric::P95Slippage => Formula::Single(Quantile("slippage", 950)),
+            Metric::PnlStddev => Formula::Single(StddevPop("pnl")),
+            Metric::MinPrice => Formula::Single(Min("price")),
+            Metric::MaxPrice => Formula::Single(Max("price")),
+            Metric::DistinctOrders => Formula::Single(Distinct("order_id")),
+            Metric::DistinctCounterparties => Formula::Single(Distinct("counterparty")),
+            Metric::DistinctAccounts => Formula::Single(Distinct("account_id")),
         }
     }
 
+    pub fn to_aggregation(&self) -> String {
+        self.formula()
+            .render(None, false)
+            .expect("every metric renders against trades_1d")
+    }
+
+    /// Same aggregation restricted to rows matching `condition`, via the `-If` combinator.
+    pub fn to_aggregation_if(&self, condition: &str) -> String {
+        self.formula()
+            .render(Some(condition), false)
+            .expect("every metric renders against trades_1d")
+    }
+
+    /// Aggregation against `trades_1d_rollup`, if the rollup's state columns cover it.
+    pub fn to_rollup_aggregation(&self, condition: Option<&str>) -> Option<String> {
+        self.formula().render(condition, true)
+    }
+
+    /// Whether values of disjoint row sets add up, so running totals of the metric are meaningful.
+    pub fn is_additive(&self) -> bool {
+        matches!(
+            self.formula(),
+            Formula::Single(Aggregate::Sum(_) | Aggregate::SumWhere(..) | Aggregate::Count)
+        )
+    }
+
+    /// Whether the metric can be NULL: ratios are, when their denominator is zero.
+    pub fn is_nullable(&self) -> bool {
+        matches!(self.formula(), Formula::Ratio { .. })
+    }
+
+    /// Whether the metric is a whole-number count, reported as an integer.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.formula(),
+            Formula::Single(Aggregate::Count | Aggregate::Distinct(_))
+        )
+    }
+
     pub fn alias(&self) -> &'static str {
         match self {
             Metric::Quantity => "total_quantity",
@@ -55,6 +250,22 @@ impl Metric {
             Metric::Slippage => "total_slippage",
             Metric::Exposure => "total_exposure",
             Metric::TradeCount => "trade_count",
+            Metric::PnlBps => "pnl_bps",
+            Metric::FeesPct => "fees_pct",
+            Metric::Vwap => "vwap",
+            Metric::WeightedVol => "weighted_vol",
+            Metric::GrossNotional => "gross_notional",
+            Metric::NetNotional => "net_notional",
+            Metric::LongNotional => "long_notional",
+            Metric::ShortNotional => "short_notional",
+            Metric::MedianSlippage => "median_slippage",
+            Metric::P95Slippage => "p95_slippage",
+            Metric::PnlStddev => "pnl_stddev",
+            Metric::MinPrice => "min_price",
+            Metric::MaxPrice => "max_price",
+            Metric::DistinctOrders => "distinct_orders",
+            Metric::DistinctCounterparties => "distinct_counterparties",
+            Metric::DistinctAccounts => "distinct_accounts",
         }
     }
 
@@ -74,6 +285,75 @@ impl Metric {
             Metric::Slippage,
             Metric::Exposure,
             Metric::TradeCount,
+            Metric::PnlBps,
+            Metric::FeesPct,
+            Metric::Vwap,
+            Metric::WeightedVol,
+            Metric::GrossNotional,
+            Metric::NetNotional,
+            Metric::LongNotional,
+            Metric::ShortNotional,
+            Metric::MedianSlippage,
+            Metric::P95Slippage,
+            Metric::PnlStddev,
+            Metric::MinPrice,
+            Metric::MaxPrice,
+            Metric::DistinctOrders,
+            Metric::DistinctCounterparties,
+            Metric::DistinctAccounts,
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditional_aggregation() {
+        assert_eq!(Metric::Notional.to_aggregation_if("desk = 'FX'"), "sumIf(notional, desk = 'FX')");
+        assert_eq!(Metric::Price.to_aggregation_if("1"), "avgIf(price, 1)");
+        assert_eq!(Metric::TradeCount.to_aggregation_if("1"), "countIf(1)");
+    }
+
+    #[test]
+    fn test_derived_metrics() {
+        assert_eq!(
+            Metric::PnlBps.to_aggregation(),
+            "sum(pnl) / nullIf(sum(notional), 0) * 10000"
+        );
+        assert_eq!(
+            Metric::Vwap.to_aggregation(),
+            "sum(price * quantity) / nullIf(sum(quantity), 0)"
+        );
+        assert_eq!(Metric::GrossNotional.to_aggregation(), "sum(abs(notional))");
+        assert_eq!(
+            Metric::LongNotional.to_aggregation_if("desk = 'FX'"),
+            "sumIf(notional, (notional > 0) AND (desk = 'FX'))"
+        );
+    }
+
+    #[test]
+    fn test_rollup_aggregation() {
+        assert_eq!(
+            Metric::PnlBps.to_rollup_aggregation(None).as_deref(),
+            Some("sumMerge(pnl_state) / nullIf(sumMerge(notional_state), 0) * 10000")
+        );
+        assert_eq!(Metric::GrossNotional.to_rollup_aggregation(None), None);
+        assert_eq!(Metric::TradeCount.to_rollup_aggregation(None), None);
+    }
+
+    #[test]
+    fn test_distribution_metrics() {
+        assert_eq!(Metric::MedianSlippage.to_aggregation(), "quantile(0.5)(slippage)");
+        assert_eq!(
+            Metric::P95Slippage.to_aggregation_if("desk = 'FX'"),
+            "quantileIf(0.95)(slippage, desk = 'FX')"
+        );
+        assert_eq!(Metric::PnlStddev.to_aggregation(), "stddevPop(pnl)");
+        assert_eq!(Metric::DistinctAccounts.to_aggregation(), "uniqExact(account_id)");
+        assert!(Metric::DistinctOrders.is_integer());
+        assert!(!Metric::DistinctOrders.is_additive());
+        assert_eq!(Metric::MaxPrice.to_rollup_aggregation(None), None);
+    }
+}
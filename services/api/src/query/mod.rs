@@ -1,7 +1,26 @@
 pub mod dimensions;
 pub mod metrics;
+pub mod attribution;
 pub mod builder;
+pub mod concentration;
+pub mod constituents;
+pub mod cursor;
+pub mod expr;
+pub mod fx;
+pub mod limits;
+pub mod lookthrough;
+pub mod params;
+pub mod planner;
+pub mod positions;
+pub mod predicate;
+pub mod stress;
+pub mod timeseries;
+pub mod var;
+pub mod window;
 
 pub use dimensions::Dimension;
 pub use metrics::Metric;
 pub use builder::PivotQueryBuilder;
+pub use params::{BoundQuery, QueryParams};
+pub use planner::SourceTable;
+pub use window::{WindowKind, WindowMetric};
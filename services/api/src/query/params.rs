@@ -0,0 +1,113 @@
+use chrono::NaiveDate;
+use clickhouse::query::Query;
+
+use crate::error::ApiError;
+
+/// ClickHouse server-side query parameters.
+///
+/// Values are sent alongside the statement as `param_<name>` settings and are
+/// referenced in the SQL text as `{name:Type}` placeholders, so user input
+/// never becomes part of the query itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryParams {
+    values: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `value` as a parameter of ClickHouse type `ty` and returns
+    /// the placeholder to splice into the SQL.
+    pub fn bind(&mut self, ty: &str, value: impl ToString) -> String {
+        let name = format!("p{}", self.values.len());
+        self.values.push((name.clone(), value.to_string()));
+        format!("{{{}:{}}}", name, ty)
+    }
+
+    /// Binds each value and returns the placeholders joined for an `IN (...)` list.
+    pub fn bind_list<T: ToString>(&mut self, ty: &str, values: &[T]) -> String {
+        values
+            .iter()
+            .map(|v| self.bind(ty, v.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parameters as HTTP interface settings (`param_p0=...`).
+    pub fn to_http_pairs(&self) -> Vec<(String, String)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (format!("param_{}", name), value.clone()))
+            .collect()
+    }
+
+    /// Attaches the parameters to a `clickhouse` crate query.
+    pub fn apply(&self, mut query: Query) -> Query {
+        for (name, value) in self.to_http_pairs() {
+            query = query.with_option(name, value);
+        }
+        query
+    }
+}
+
+/// A SQL statement together with the parameters it references.
+#[derive(Debug, Clone, Default)]
+pub struct BoundQuery {
+    pub sql: String,
+    pub params: QueryParams,
+}
+
+impl BoundQuery {
+    pub fn new(sql: String, params: QueryParams) -> Self {
+        Self { sql, params }
+    }
+
+    pub fn to_query(&self, client: &clickhouse::Client) -> Query {
+        self.params.apply(client.query(&self.sql))
+    }
+}
+
+/// Rejects anything that is not a `YYYY-MM-DD` date, naming the offending field.
+pub fn validate_date(field: &str, value: &str) -> Result<(), ApiError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|_| ())
+        .map_err(|_| {
+            ApiError::QueryValidation(format!(
+                "Invalid {}: '{}'. Expected YYYY-MM-DD",
+                field, value
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_returns_typed_placeholders() {
+        let mut params = QueryParams::new();
+        assert_eq!(params.bind("String", "BRK.B"), "{p0:String}");
+        assert_eq!(params.bind_list("UInt32", &[1u32, 2]), "{p1:UInt32}, {p2:UInt32}");
+        assert_eq!(params.get("p0"), Some("BRK.B"));
+        assert_eq!(
+            params.to_http_pairs()[2],
+            ("param_p2".to_string(), "2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_date() {
+        assert!(validate_date("trade_date", "2024-01-15").is_ok());
+        assert!(validate_date("trade_date", "2024-01-15' OR 1=1").is_err());
+        assert!(validate_date("trade_date", "2024-13-01").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::request::PivotFilters;
+use crate::query::lookthrough::LOOK_THROUGH_SOURCE;
+use crate::query::{Dimension, Metric};
+
+/// Table a pivot query is read from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceTable {
+    #[default]
+    Trades,
+    /// `pivot.trades_1d_rollup`, the `AggregatingMergeTree` fed by `trades_1d_rollup_mv`.
+    Rollup,
+    /// `pivot.trades_1d` with ETF/ETC trades split into constituents at query time.
+    LookThrough,
+}
+
+/// Dimensions stored as key columns of `trades_1d_rollup`.
+const ROLLUP_DIMENSIONS: &[Dimension] = &[
+    Dimension::TradeDate,
+    Dimension::PortfolioManagerId,
+    Dimension::FundId,
+    Dimension::Book,
+    Dimension::AssetClass,
+    Dimension::Symbol,
+    // Buckets of trade_date
+    Dimension::TradeWeek,
+    Dimension::TradeMonth,
+    Dimension::TradeQuarter,
+];
+
+impl SourceTable {
+    /// Picks the rollup when every dimension, filter and metric can be answered from it.
+    pub fn plan(dimensions: &[Dimension], metrics: &[Metric], filters: &PivotFilters) -> Self {
+        let dimensions_covered = dimensions.iter().all(|d| ROLLUP_DIMENSIONS.contains(d));
+        let metrics_covered = metrics.iter().all(|m| Self::Rollup.aggregation(m).is_some());
+
+        if dimensions_covered && metrics_covered && Self::rollup_covers_filters(filters) {
+            SourceTable::Rollup
+        } else {
+            SourceTable::Trades
+        }
+    }
+
+    /// `FROM` clause for this source.
+    pub fn table(&self) -> &'static str {
+        match self {
+            SourceTable::LookThrough => LOOK_THROUGH_SOURCE.as_str(),
+            _ => self.name(),
+        }
+    }
+
+    /// Name reported in response metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceTable::Trades => "pivot.trades_1d",
+            SourceTable::Rollup => "pivot.trades_1d_rollup",
+            SourceTable::LookThrough => "pivot.trades_1d+pivot.constituents",
+        }
+    }
+
+    /// Aggregate expression for `metric` against this table, if the table can serve it.
+    ///
+    /// Look-through splits a trade into one row per leg, so trades are counted by id.
+    pub fn aggregation(&self, metric: &Metric) -> Option<String> {
+        match self {
+            SourceTable::LookThrough if *metric == Metric::TradeCount => {
+                Some("uniqExact(trade_id)".to_string())
+            }
+            SourceTable::Trades | SourceTable::LookThrough => Some(metric.to_aggregation()),
+            SourceTable::Rollup => metric.to_rollup_aggregation(None),
+        }
+    }
+
+    /// Conditional form of [`SourceTable::aggregation`] for cross-tab cells.
+    pub fn aggregation_if(&self, metric: &Metric, condition: &str) -> Option<String> {
+        match self {
+            SourceTable::LookThrough if *metric == Metric::TradeCount => {
+                Some(format!("uniqExactIf(trade_id, {})", condition))
+            }
+            SourceTable::Trades | SourceTable::LookThrough => {
+                Some(metric.to_aggregation_if(condition))
+            }
+            SourceTable::Rollup => metric.to_rollup_aggregation(Some(condition)),
+        }
+    }
+
+    /// Every filter must be on a rollup key column; measure ranges need the raw rows.
+    fn rollup_covers_filters(filters: &PivotFilters) -> bool {
+        filters
+            .to_predicates()
+            .iter()
+            .all(|p| ROLLUP_DIMENSIONS.iter().any(|d| d.to_column() == p.field()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covered_query_uses_rollup() {
+        let filters = PivotFilters {
+            trade_date: Some("2024-01-15".to_string()),
+            book: Some(vec!["Alpha".to_string()]),
+            ..Default::default()
+        };
+        let plan = SourceTable::plan(
+            &[Dimension::PortfolioManagerId, Dimension::Symbol],
+            &[Metric::Notional, Metric::Pnl],
+            &filters,
+        );
+        assert_eq!(plan, SourceTable::Rollup);
+    }
+
+    #[test]
+    fn test_uncovered_queries_use_trades() {
+        let none = PivotFilters::default();
+        assert_eq!(
+            SourceTable::plan(&[Dimension::Desk], &[Metric::Notional], &none),
+            SourceTable::Trades
+        );
+        assert_eq!(
+            SourceTable::plan(&[Dimension::Book], &[Metric::TradeCount], &none),
+            SourceTable::Trades
+        );
+
+        let filters = PivotFilters {
+            desk: Some(vec!["FX".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            SourceTable::plan(&[Dimension::Book], &[Metric::Notional], &filters),
+            SourceTable::Trades
+        );
+    }
+
+    #[test]
+    fn test_measure_range_filter_uses_trades() {
+        let filters = PivotFilters {
+            predicates: vec![crate::models::request::Predicate::new(
+                crate::models::request::Condition::Between {
+                    field: "notional".to_string(),
+                    min: Some(serde_json::json!(0)),
+                    max: None,
+                },
+            )],
+            ..Default::default()
+        };
+        assert_eq!(
+            SourceTable::plan(&[Dimension::Book], &[Metric::Notional], &filters),
+            SourceTable::Trades
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Net positions as of a date, built from the daily legs in `pivot.positions_1d`.
+//!
+//! Each position is replayed day by day up to `as_of`, buys before sells, keeping
+//! an average cost for the open quantity. Quantity reducing the position realises
+//! `(price - avg_cost)` per unit; a position that flips side restarts at the
+//! price it flipped at. The open quantity is marked at the last traded price.
+//! Amounts stay in each instrument's currency, so totals are summarised per currency.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::request::PositionsQuery;
+use crate::models::response::{Position, PositionSide, PositionSummary};
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+
+/// Quantities smaller than this are treated as flat.
+const FLAT_EPSILON: f64 = 1e-9;
+
+/// Most positions returned in one response.
+const MAX_POSITIONS: u32 = 10_000;
+
+pub struct PositionsQueryBuilder {
+    query: PositionsQuery,
+}
+
+/// Positions replayed as of the query date.
+#[derive(Debug)]
+pub struct NetPositions {
+    /// One summary per currency, in currency order.
+    pub summary: Vec<PositionSummary>,
+    /// Positions in scope before `limit` applied.
+    pub total: usize,
+    pub data: Vec<Position>,
+}
+
+/// Open quantity and average cost of one position while it is replayed.
+#[derive(Debug, Default)]
+struct Book {
+    quantity: f64,
+    avg_cost: f64,
+    realised_pnl: f64,
+}
+
+impl Book {
+    /// Applies a trade of signed `quantity` at `price`.
+    fn apply(&mut self, quantity: f64, price: f64) {
+        if quantity.abs() < FLAT_EPSILON {
+            return;
+        }
+        if self.quantity.abs() < FLAT_EPSILON || self.quantity.signum() == quantity.signum() {
+            let open = self.quantity + quantity;
+            self.avg_cost = (self.quantity * self.avg_cost + quantity * price) / open;
+            self.quantity = open;
+            return;
+        }
+
+        let closed = quantity.abs().min(self.quantity.abs());
+        self.realised_pnl += closed * (price - self.avg_cost) * self.quantity.signum();
+        let open = self.quantity + quantity;
+        if open.abs() < FLAT_EPSILON {
+            self.quantity = 0.0;
+            self.avg_cost = 0.0;
+        } else {
+            if open.signum() != self.quantity.signum() {
+                self.avg_cost = price;
+            }
+            self.quantity = open;
+        }
+    }
+}
+
+impl PositionsQueryBuilder {
+    pub fn from_query(query: &PositionsQuery) -> Result<Self, ApiError> {
+        validate_date("as_of", &query.as_of)?;
+        // Every position is replayed from its first trade, so the scan is bounded
+        // by scope rather than by date.
+        if query.fund_id.is_none() && query.portfolio_manager_id.is_none() && query.symbol.is_none() {
+            return Err(ApiError::QueryValidation(
+                "Positions need a fund_id, portfolio_manager_id or symbol filter".to_string(),
+            ));
+        }
+        if query.limit == 0 || query.limit > MAX_POSITIONS {
+            return Err(ApiError::QueryValidation(format!(
+                "limit must be between 1 and {}",
+                MAX_POSITIONS
+            )));
+        }
+
+        Ok(Self { query: query.clone() })
+    }
+
+    /// Daily buy and sell legs of every position in scope, oldest day first.
+    pub fn build(&self) -> BoundQuery {
+        let mut params = QueryParams::new();
+        let mut conditions = vec![format!("trade_date <= {}", params.bind("Date", &self.query.as_of))];
+        if let Some(fund_id) = self.query.fund_id {
+            conditions.push(format!("fund_id = {}", params.bind("UInt32", fund_id)));
+        }
+        if let Some(pm_id) = self.query.portfolio_manager_id {
+            conditions.push(format!("portfolio_manager_id = {}", params.bind("UInt32", pm_id)));
+        }
+        if let Some(symbol) = &self.query.symbol {
+            conditions.push(format!("symbol = {}", params.bind("String", symbol)));
+        }
+        let level = self.query.level.to_column();
+
+        let sql = format!(
+            "SELECT {level}, symbol, any(asset_class) AS asset_class, any(currency) AS currency, \
+             toString(trade_date) AS day, \
+             sumMerge(buy_quantity_state) AS buy_quantity, sumMerge(buy_notional_state) AS buy_notional, \
+             sumMerge(sell_quantity_state) AS sell_quantity, sumMerge(sell_notional_state) AS sell_notional, \
+             argMaxMerge(last_price_state) AS last_price \
+             FROM pivot.positions_1d WHERE {where} \
+             GROUP BY {level}, symbol, trade_date \
+             ORDER BY {level}, symbol, trade_date",
+            level = level,
+            where = conditions.join(" AND "),
+        );
+
+        BoundQuery::new(sql, params)
+    }
+
+    /// Positions replayed from the rows of [`Self::build`], largest market value
+    /// first, with totals over all of them.
+    pub fn net(&self, rows: Vec<HashMap<String, Value>>) -> NetPositions {
+        let level = self.query.level.to_column();
+        let number = |row: &HashMap<String, Value>, key: &str| -> f64 {
+            row.get(key)
+                .and_then(|v| v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+                .unwrap_or(0.0)
+        };
+        let text = |row: &mut HashMap<String, Value>, key: &str| -> String {
+            match row.remove(key) {
+                Some(Value::String(s)) => s,
+                _ => String::new(),
+            }
+        };
+
+        let mut positions: Vec<(Book, Position)> = Vec::new();
+        for mut row in rows {
+            let group = row.remove(level).unwrap_or(Value::Null);
+            let symbol = text(&mut row, "symbol");
+            let trade_date = text(&mut row, "day");
+
+            let is_same = positions
+                .last()
+                .is_some_and(|(_, p)| p.group == group && p.symbol == symbol);
+            if !is_same {
+                let position = Position {
+                    group,
+                    asset_class: text(&mut row, "asset_class"),
+                    currency: text(&mut row, "currency"),
+                    symbol,
+                    side: PositionSide::Flat,
+                    quantity: 0.0,
+                    day_quantity: 0.0,
+                    avg_cost: 0.0,
+                    last_price: 0.0,
+                    market_value: 0.0,
+                    realised_pnl: 0.0,
+                    unrealised_pnl: 0.0,
+                    last_trade_date: String::new(),
+                };
+                positions.push((Book::default(), position));
+            }
+            let (book, position) = positions.last_mut().expect("position just pushed");
+
+            let (buy_quantity, sell_quantity) = (number(&row, "buy_quantity"), number(&row, "sell_quantity"));
+            if buy_quantity > 0.0 {
+                book.apply(buy_quantity, number(&row, "buy_notional") / buy_quantity);
+            }
+            if sell_quantity > 0.0 {
+                book.apply(-sell_quantity, number(&row, "sell_notional") / sell_quantity);
+            }
+            position.day_quantity = if trade_date == self.query.as_of {
+                buy_quantity - sell_quantity
+            } else {
+                0.0
+            };
+            position.last_price = number(&row, "last_price");
+            position.last_trade_date = trade_date;
+        }
+
+        let mut summaries: BTreeMap<String, PositionSummary> = BTreeMap::new();
+        let mut data: Vec<Position> = positions
+            .into_iter()
+            .map(|(book, mut position)| {
+                position.quantity = book.quantity;
+                position.avg_cost = book.avg_cost;
+                position.realised_pnl = book.realised_pnl;
+                position.market_value = book.quantity * position.last_price;
+                position.unrealised_pnl = book.quantity * (position.last_price - book.avg_cost);
+                position.side = if book.quantity.abs() < FLAT_EPSILON {
+                    PositionSide::Flat
+                } else if book.quantity > 0.0 {
+                    PositionSide::Long
+                } else {
+                    PositionSide::Short
+                };
+
+                let summary = summaries
+                    .entry(position.currency.clone())
+                    .or_insert_with(|| PositionSummary {
+                        currency: position.currency.clone(),
+                        ..Default::default()
+                    });
+                match position.side {
+                    PositionSide::Long => {
+                        summary.long_positions += 1;
+                        summary.long_market_value += position.market_value;
+                    }
+                    PositionSide::Short => {
+                        summary.short_positions += 1;
+                        summary.short_market_value += position.market_value;
+                    }
+                    PositionSide::Flat => {}
+                }
+                summary.realised_pnl += position.realised_pnl;
+                summary.unrealised_pnl += position.unrealised_pnl;
+                position
+            })
+            .filter(|p| self.query.include_flat || p.side != PositionSide::Flat)
+            .collect();
+
+        let summary: Vec<PositionSummary> = summaries
+            .into_values()
+            .map(|mut summary| {
+                summary.gross_market_value = summary.long_market_value - summary.short_market_value;
+                summary.net_market_value = summary.long_market_value + summary.short_market_value;
+                summary
+            })
+            .collect();
+
+        let total = data.len();
+        data.sort_by(|a, b| b.market_value.abs().total_cmp(&a.market_value.abs()));
+        data.truncate(self.query.limit as usize);
+        NetPositions { summary, total, data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query() -> PositionsQuery {
+        serde_json::from_value(json!({"as_of": "2024-01-16", "fund_id": 3})).unwrap()
+    }
+
+    fn leg(account: u64, symbol: &str, date: &str, buy: (f64, f64), sell: (f64, f64), last: f64) -> HashMap<String, Value> {
+        let currency = if symbol.ends_with(".L") { "GBP" } else { "USD" };
+        HashMap::from([
+            ("account_id".to_string(), json!(account.to_string())),
+            ("symbol".to_string(), json!(symbol)),
+            ("asset_class".to_string(), json!("Equity")),
+            ("currency".to_string(), json!(currency)),
+            ("day".to_string(), json!(date)),
+            ("buy_quantity".to_string(), json!(buy.0)),
+            ("buy_notional".to_string(), json!(buy.0 * buy.1)),
+            ("sell_quantity".to_string(), json!(sell.0)),
+            ("sell_notional".to_string(), json!(sell.0 * sell.1)),
+            ("last_price".to_string(), json!(last)),
+        ])
+    }
+
+    #[test]
+    fn test_positions_query() {
+        let bound = PositionsQueryBuilder::from_query(&query()).unwrap().build();
+
+        assert!(bound.sql.starts_with("SELECT account_id, symbol, any(asset_class) AS asset_class"));
+        assert!(bound.sql.contains(
+            "FROM pivot.positions_1d WHERE trade_date <= {p0:Date} AND fund_id = {p1:UInt32} \
+             GROUP BY account_id, symbol, trade_date"
+        ));
+        assert!(bound.sql.contains("toString(trade_date) AS day"));
+        assert_eq!(bound.params.get("p1"), Some("3"));
+    }
+
+    #[test]
+    fn test_unscoped_positions_rejected() {
+        let q: PositionsQuery = serde_json::from_value(json!({"as_of": "2024-01-16"})).unwrap();
+        assert!(PositionsQueryBuilder::from_query(&q).is_err());
+
+        let q: PositionsQuery =
+            serde_json::from_value(json!({"as_of": "2024-01-16", "symbol": "AAPL"})).unwrap();
+        assert!(PositionsQueryBuilder::from_query(&q).is_ok());
+    }
+
+    #[test]
+    fn test_running_position_and_average_cost() {
+        let builder = PositionsQueryBuilder::from_query(&query()).unwrap();
+        let rows = vec![
+            // Buy 100 @ 10 and 100 @ 20, then sell 50 @ 30 the next day
+            leg(1, "AAPL", "2024-01-15", (200.0, 15.0), (0.0, 0.0), 20.0),
+            leg(1, "AAPL", "2024-01-16", (0.0, 0.0), (50.0, 30.0), 30.0),
+            // Short 40 @ 50
+            leg(1, "MSFT", "2024-01-15", (0.0, 0.0), (40.0, 50.0), 45.0),
+        ];
+        let NetPositions { summary, total, data } = builder.net(rows);
+        assert_eq!(total, 2);
+
+        let aapl = data.iter().find(|p| p.symbol == "AAPL").unwrap();
+        assert_eq!(aapl.side, PositionSide::Long);
+        assert_eq!(aapl.quantity, 150.0);
+        assert_eq!(aapl.day_quantity, -50.0);
+        assert_eq!(aapl.avg_cost, 15.0);
+        assert_eq!(aapl.realised_pnl, 750.0);
+        assert_eq!(aapl.unrealised_pnl, 2250.0);
+        assert_eq!(aapl.last_trade_date, "2024-01-16");
+
+        let msft = data.iter().find(|p| p.symbol == "MSFT").unwrap();
+        assert_eq!(msft.side, PositionSide::Short);
+        assert_eq!(msft.market_value, -1800.0);
+        assert_eq!(msft.unrealised_pnl, 200.0);
+        assert_eq!(msft.day_quantity, 0.0);
+
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].currency, "USD");
+        assert_eq!(summary[0].long_positions, 1);
+        assert_eq!(summary[0].short_positions, 1);
+        assert_eq!(summary[0].gross_market_value, 4500.0 + 1800.0);
+        assert_eq!(summary[0].net_market_value, 4500.0 - 1800.0);
+    }
+
+    #[test]
+    fn test_summary_is_per_currency() {
+        let builder = PositionsQueryBuilder::from_query(&query()).unwrap();
+        let rows = vec![
+            leg(1, "AAPL", "2024-01-15", (10.0, 100.0), (0.0, 0.0), 100.0),
+            leg(1, "VOD.L", "2024-01-15", (0.0, 0.0), (100.0, 70.0), 70.0),
+        ];
+        let summary = builder.net(rows).summary;
+
+        // Dollars and pounds are never added together
+        let currencies: Vec<&str> = summary.iter().map(|s| s.currency.as_str()).collect();
+        assert_eq!(currencies, vec!["GBP", "USD"]);
+        assert_eq!(summary[0].net_market_value, -7000.0);
+        assert_eq!(summary[1].net_market_value, 1000.0);
+    }
+
+    #[test]
+    fn test_flip_and_flat_positions() {
+        let mut book = Book::default();
+        book.apply(100.0, 10.0);
+        book.apply(-150.0, 12.0);
+        assert_eq!(book.quantity, -50.0);
+        assert_eq!(book.avg_cost, 12.0);
+        assert_eq!(book.realised_pnl, 200.0);
+
+        let builder = PositionsQueryBuilder::from_query(&query()).unwrap();
+        let rows = vec![leg(2, "TSLA", "2024-01-15", (10.0, 100.0), (10.0, 90.0), 90.0)];
+        let NetPositions { summary, data, .. } = builder.net(rows);
+        assert!(data.is_empty());
+        assert_eq!(summary[0].realised_pnl, -100.0);
+
+        let mut q = query();
+        q.include_flat = true;
+        let rows = vec![leg(2, "TSLA", "2024-01-15", (10.0, 100.0), (10.0, 90.0), 90.0)];
+        let data = PositionsQueryBuilder::from_query(&q).unwrap().net(rows).data;
+        assert_eq!(data[0].side, PositionSide::Flat);
+        assert_eq!(data[0].group, json!("2"));
+    }
+}
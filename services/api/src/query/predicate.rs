@@ -0,0 +1,210 @@
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::request::{Condition, Predicate};
+use crate::query::expr::MEASURE_COLUMNS;
+use crate::query::params::{validate_date, QueryParams};
+use crate::query::Dimension;
+
+/// Compiles `predicate` into a `WHERE` clause term, binding every value.
+///
+/// Values are checked against the column's type here, so a bad value is a
+/// validation error rather than a ClickHouse parse failure.
+pub fn compile_predicate(
+    predicate: &Predicate,
+    params: &mut QueryParams,
+) -> Result<String, ApiError> {
+    let clause = match &predicate.condition {
+        Condition::Eq { dimension, value } => {
+            let ty = dimension.clickhouse_type();
+            let value = scalar(dimension.to_column(), ty, value)?;
+            format!("{} = {}", dimension.to_expr(), params.bind(ty, value))
+        }
+        Condition::In { dimension, values } => compile_in(*dimension, values, "IN", params)?,
+        Condition::NotIn { dimension, values } => compile_in(*dimension, values, "NOT IN", params)?,
+        Condition::Prefix { dimension, value } => {
+            require_string(*dimension, "prefix")?;
+            format!(
+                "startsWith({}, {})",
+                dimension.to_expr(),
+                params.bind("String", value)
+            )
+        }
+        Condition::IsEmpty { dimension } => {
+            require_string(*dimension, "is_empty")?;
+            format!("empty({})", dimension.to_expr())
+        }
+        Condition::Between { field, min, max } => {
+            let (column, expr, ty) = resolve_field(field)?;
+            if min.is_none() && max.is_none() {
+                return Err(ApiError::QueryValidation(format!(
+                    "between filter on '{}' needs a min or a max",
+                    field
+                )));
+            }
+
+            let mut bounds = Vec::new();
+            if let Some(min) = min {
+                let value = scalar(column, ty, min)?;
+                bounds.push(format!("{} >= {}", expr, params.bind(ty, value)));
+            }
+            if let Some(max) = max {
+                let value = scalar(column, ty, max)?;
+                bounds.push(format!("{} <= {}", expr, params.bind(ty, value)));
+            }
+            bounds.join(" AND ")
+        }
+    };
+
+    Ok(if predicate.negate {
+        format!("NOT ({})", clause)
+    } else {
+        clause
+    })
+}
+
+fn compile_in(
+    dimension: Dimension,
+    values: &[Value],
+    op: &str,
+    params: &mut QueryParams,
+) -> Result<String, ApiError> {
+    let column = dimension.to_column();
+    let ty = dimension.clickhouse_type();
+
+    let values = values
+        .iter()
+        .map(|v| scalar(column, ty, v))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if values.is_empty() {
+        return Err(ApiError::QueryValidation(format!(
+            "Filter on '{}' needs at least one value",
+            column
+        )));
+    }
+
+    Ok(format!(
+        "{} {} ({})",
+        dimension.to_expr(),
+        op,
+        params.bind_list(ty, &values)
+    ))
+}
+
+/// Resolves a range field to its name, SQL expression and bind type: a dimension,
+/// or a measure bound as `Float64`.
+fn resolve_field(field: &str) -> Result<(&'static str, &'static str, &'static str), ApiError> {
+    if let Some(dim) = Dimension::all().iter().find(|d| d.to_column() == field) {
+        return Ok((dim.to_column(), dim.to_expr(), dim.clickhouse_type()));
+    }
+    MEASURE_COLUMNS
+        .iter()
+        .find(|c| **c == field)
+        .map(|c| (*c, *c, "Float64"))
+        .ok_or_else(|| {
+            ApiError::QueryValidation(format!(
+                "Invalid filter field: '{}'. Expected a dimension or one of {:?}",
+                field, MEASURE_COLUMNS
+            ))
+        })
+}
+
+fn require_string(dimension: Dimension, op: &str) -> Result<(), ApiError> {
+    if dimension.clickhouse_type() == "String" {
+        Ok(())
+    } else {
+        Err(ApiError::QueryValidation(format!(
+            "'{}' filters only apply to text dimensions, not '{}'",
+            op,
+            dimension.to_column()
+        )))
+    }
+}
+
+/// Textual form of a filter value, checked against the column's ClickHouse type.
+fn scalar(column: &str, ty: &str, value: &Value) -> Result<String, ApiError> {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => {
+            return Err(ApiError::QueryValidation(format!(
+                "Filter values for '{}' must be strings or numbers",
+                column
+            )))
+        }
+    };
+
+    let valid = match ty {
+        "Date" => return validate_date(column, &text).map(|_| text),
+        "DateTime" => NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S").is_ok(),
+        "UInt32" => text.parse::<u32>().is_ok(),
+        "UInt64" => text.parse::<u64>().is_ok(),
+        "Float64" => text.parse::<f64>().is_ok_and(f64::is_finite),
+        _ => true,
+    };
+
+    if valid {
+        Ok(text)
+    } else {
+        Err(ApiError::QueryValidation(format!(
+            "Invalid {}: '{}'. Expected {}",
+            column, text, ty
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn compile(value: Value) -> Result<(String, QueryParams), ApiError> {
+        let predicate: Predicate = serde_json::from_value(value).unwrap();
+        let mut params = QueryParams::new();
+        compile_predicate(&predicate, &mut params).map(|sql| (sql, params))
+    }
+
+    #[test]
+    fn test_predicate_operators() {
+        let (sql, params) =
+            compile(json!({"op": "not_in", "dimension": "strategy", "values": ["Macro", "Arb"]}))
+                .unwrap();
+        assert_eq!(sql, "strategy NOT IN ({p0:String}, {p1:String})");
+        assert_eq!(params.get("p1"), Some("Arb"));
+
+        let (sql, _) =
+            compile(json!({"op": "prefix", "dimension": "book", "value": "EQ-"})).unwrap();
+        assert_eq!(sql, "startsWith(book, {p0:String})");
+
+        let (sql, _) =
+            compile(json!({"op": "is_empty", "dimension": "parent_symbol", "negate": true}))
+                .unwrap();
+        assert_eq!(sql, "NOT (empty(parent_symbol))");
+
+        let (sql, params) =
+            compile(json!({"op": "between", "field": "notional", "min": 1000000})).unwrap();
+        assert_eq!(sql, "notional >= {p0:Float64}");
+        assert_eq!(params.get("p0"), Some("1000000"));
+
+        let (sql, _) =
+            compile(json!({"op": "in", "dimension": "account_id", "values": [42]})).unwrap();
+        assert_eq!(sql, "account_id IN ({p0:UInt64})");
+    }
+
+    #[test]
+    fn test_invalid_predicates_rejected() {
+        assert!(compile(json!({"op": "in", "dimension": "desk", "values": []})).is_err());
+        assert!(
+            compile(json!({"op": "in", "dimension": "fund_id", "values": ["1 OR 1=1"]})).is_err()
+        );
+        assert!(compile(json!({"op": "prefix", "dimension": "fund_id", "value": "1"})).is_err());
+        assert!(
+            compile(json!({"op": "between", "field": "trade_date", "min": "yesterday"})).is_err()
+        );
+        assert!(compile(json!({"op": "between", "field": "pnl"})).is_err());
+        assert!(compile(json!({"op": "between", "field": "secret", "max": 1})).is_err());
+        assert!(compile(json!({"op": "in", "dimension": "desk", "values": [null]})).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Taylor-approximated P&L under market shocks.
+//!
+//! Greeks are per unit of the position, so each contribution is scaled by
+//! `quantity`:
+//!
+//! `quantity · (delta·ΔS + ½·gamma·ΔS² + vega·Δσ + rho·Δr + theta·Δt)`
+//!
+//! where `ΔS` is `price · spot_pct / 100`, `Δσ` is in vol points, `Δr` in percent
+//! (`rate_bp / 100`) and `Δt` in days. Every row takes the most specific shock that
+//! matches it; rows no shock matches are left unshocked.
+//!
+//! One `trade_date` is stressed at a time, and only top-level rows, so constituent
+//! rows exploded from an ETF/ETC trade never repeat its P&L.
+
+use crate::error::ApiError;
+use crate::models::request::{Shock, StressRequest};
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+use crate::query::predicate::compile_predicate;
+use crate::query::Dimension;
+
+/// Rows stressed: traded positions, not the constituent rows exploded from them.
+const TOP_LEVEL_EXPOSURES: &str = "exposure_type IN ('Direct', 'ETF', 'ETC')";
+
+/// Most groups a stress query returns.
+const MAX_STRESS_ROWS: u32 = 10_000;
+
+/// Largest shock magnitudes accepted, as a guard against unit mistakes
+/// (e.g. a rate move of `0.25` meant as 25bp is fine; `2500` is not).
+const MAX_SPOT_PCT: f64 = 100.0;
+const MAX_VOL_POINTS: f64 = 100.0;
+const MAX_RATE_BP: f64 = 1_000.0;
+const MAX_DAYS: f64 = 365.0;
+
+/// Shock together with the `scenario` rows it applies to; `None` applies to all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopedShock {
+    pub scenario: Option<String>,
+    pub shock: Shock,
+}
+
+/// Checks shocks before they are stored or applied.
+pub fn validate_shocks(shocks: &[Shock]) -> Result<(), ApiError> {
+    for shock in shocks {
+        for (name, value, max) in [
+            ("spot_pct", shock.spot_pct, MAX_SPOT_PCT),
+            ("vol_points", shock.vol_points, MAX_VOL_POINTS),
+            ("rate_bp", shock.rate_bp, MAX_RATE_BP),
+            ("days", shock.days, MAX_DAYS),
+        ] {
+            if !value.is_finite() || value.abs() > max {
+                return Err(ApiError::QueryValidation(format!(
+                    "{} must be within ±{}, got {}",
+                    name, max, value
+                )));
+            }
+        }
+        if shock.spot_pct <= -MAX_SPOT_PCT {
+            return Err(ApiError::QueryValidation(
+                "spot_pct must be greater than -100".to_string(),
+            ));
+        }
+        if shock.days < 0.0 {
+            return Err(ApiError::QueryValidation("days must not be negative".to_string()));
+        }
+        if shock.asset_class.is_some() && shock.underlying_symbol.is_some() {
+            return Err(ApiError::QueryValidation(
+                "A shock is scoped by asset_class or underlying_symbol, not both".to_string(),
+            ));
+        }
+        if shock.asset_class.as_deref().or(shock.underlying_symbol.as_deref()) == Some("") {
+            return Err(ApiError::QueryValidation(
+                "Shock scope must not be empty".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub struct StressQueryBuilder {
+    request: StressRequest,
+    shocks: Vec<ScopedShock>,
+}
+
+impl StressQueryBuilder {
+    /// `shocks` are the resolved shocks: the request's own, or those of the stored
+    /// set(s) it names.
+    pub fn new(request: &StressRequest, mut shocks: Vec<ScopedShock>) -> Result<Self, ApiError> {
+        let sources = [
+            request.shock_set.is_some(),
+            !request.shocks.is_empty(),
+            request.by_row_scenario,
+        ];
+        if sources.iter().filter(|s| **s).count() != 1 {
+            return Err(ApiError::QueryValidation(
+                "Specify exactly one of shock_set, shocks or by_row_scenario".to_string(),
+            ));
+        }
+        match (&request.filters.trade_date, &request.filters.trade_date_range) {
+            (Some(date), None) => validate_date("filters.trade_date", date)?,
+            _ => {
+                return Err(ApiError::QueryValidation(
+                    "Stress applies to a single filters.trade_date".to_string(),
+                ))
+            }
+        }
+        if request.limit == 0 || request.limit > MAX_STRESS_ROWS {
+            return Err(ApiError::QueryValidation(format!(
+                "limit must be between 1 and {}",
+                MAX_STRESS_ROWS
+            )));
+        }
+        let shock_list: Vec<Shock> = shocks.iter().map(|s| s.shock.clone()).collect();
+        validate_shocks(&shock_list)?;
+
+        // Most specific first, so the first matching branch of `multiIf` wins.
+        shocks.sort_by_key(|s| std::cmp::Reverse(specificity(&s.shock)));
+
+        Ok(Self {
+            request: request.clone(),
+            shocks,
+        })
+    }
+
+    pub fn dimensions(&self) -> &[Dimension] {
+        &self.request.dimensions
+    }
+
+    pub fn build(&self) -> Result<BoundQuery, ApiError> {
+        let mut params = QueryParams::new();
+
+        let mut where_clauses = Vec::new();
+        for predicate in self.request.filters.to_predicates() {
+            where_clauses.push(compile_predicate(&predicate, &mut params)?);
+        }
+        where_clauses.push(TOP_LEVEL_EXPOSURES.to_string());
+
+        let conditions: Vec<String> = self
+            .shocks
+            .iter()
+            .map(|s| match_condition(s, &mut params))
+            .collect();
+        let mut factor = |value: fn(&Shock) -> f64| -> String {
+            if self.shocks.is_empty() {
+                return "0".to_string();
+            }
+            let branches: Vec<String> = self
+                .shocks
+                .iter()
+                .zip(&conditions)
+                .map(|(s, cond)| format!("{}, {}", cond, params.bind("Float64", value(&s.shock))))
+                .collect();
+            format!("multiIf({}, 0)", branches.join(", "))
+        };
+        let spot = factor(|s| s.spot_pct);
+        let vol = factor(|s| s.vol_points);
+        let rate = factor(|s| s.rate_bp);
+        let days = factor(|s| s.days);
+
+        let dims = &self.request.dimensions;
+        let select_dims: String = dims.iter().map(|d| format!("{}, ", d.to_select())).collect();
+        let group_by = if dims.is_empty() {
+            String::new()
+        } else {
+            let columns: Vec<&str> = dims.iter().map(|d| d.to_column()).collect();
+            format!(" GROUP BY {}", columns.join(", "))
+        };
+        let where_sql = format!(" WHERE {}", where_clauses.join(" AND "));
+
+        let sql = format!(
+            "SELECT {dims}sum(pnl) AS base_pnl, \
+             sum(quantity * delta * d_spot) AS delta_pnl, \
+             sum(0.5 * quantity * gamma * d_spot * d_spot) AS gamma_pnl, \
+             sum(quantity * vega * d_vol) AS vega_pnl, \
+             sum(quantity * rho * d_rate) AS rho_pnl, \
+             sum(quantity * theta * d_days) AS theta_pnl, \
+             delta_pnl + gamma_pnl + vega_pnl + rho_pnl + theta_pnl AS stress_pnl \
+             FROM (SELECT *, price * {spot} / 100 AS d_spot, {vol} AS d_vol, \
+             {rate} / 100 AS d_rate, {days} AS d_days \
+             FROM pivot.trades_1d{where_sql}){group_by} \
+             ORDER BY stress_pnl ASC LIMIT {limit}",
+            dims = select_dims,
+            spot = spot,
+            vol = vol,
+            rate = rate,
+            days = days,
+            where_sql = where_sql,
+            group_by = group_by,
+            limit = self.request.limit,
+        );
+
+        Ok(BoundQuery::new(sql, params))
+    }
+}
+
+/// Rank used to order shocks: an underlying beats an asset class beats no scope.
+fn specificity(shock: &Shock) -> u8 {
+    if shock.underlying_symbol.is_some() {
+        2
+    } else if shock.asset_class.is_some() {
+        1
+    } else {
+        0
+    }
+}
+
+/// `multiIf` condition selecting the rows `shock` applies to.
+fn match_condition(shock: &ScopedShock, params: &mut QueryParams) -> String {
+    let mut terms = Vec::new();
+    if let Some(scenario) = &shock.scenario {
+        terms.push(format!("scenario = {}", params.bind("String", scenario)));
+    }
+    if let Some(symbol) = &shock.shock.underlying_symbol {
+        terms.push(format!("underlying_symbol = {}", params.bind("String", symbol)));
+    }
+    if let Some(asset_class) = &shock.shock.asset_class {
+        terms.push(format!("asset_class = {}", params.bind("String", asset_class)));
+    }
+    if terms.is_empty() {
+        "1".to_string()
+    } else {
+        terms.join(" AND ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(body: serde_json::Value) -> StressRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn scoped(shock: Shock) -> ScopedShock {
+        ScopedShock { scenario: None, shock }
+    }
+
+    #[test]
+    fn test_stress_query() {
+        let req = request(json!({
+            "dimensions": ["asset_class"],
+            "filters": {"trade_date": "2024-01-15"},
+            "shocks": [{"spot_pct": -10, "vol_points": 5, "rate_bp": 25}]
+        }));
+        let shocks = req.shocks.iter().cloned().map(scoped).collect();
+        let bound = StressQueryBuilder::new(&req, shocks).unwrap().build().unwrap();
+
+        assert!(bound.sql.starts_with("SELECT asset_class, sum(pnl) AS base_pnl"));
+        assert!(bound.sql.contains("price * multiIf(1, {p1:Float64}, 0) / 100 AS d_spot"));
+        assert!(bound.sql.contains(
+            "FROM pivot.trades_1d WHERE trade_date = {p0:Date} \
+             AND exposure_type IN ('Direct', 'ETF', 'ETC')) GROUP BY asset_class"
+        ));
+        assert!(bound.sql.ends_with("ORDER BY stress_pnl ASC LIMIT 100"));
+        assert_eq!(bound.params.get("p1"), Some("-10"));
+        assert_eq!(bound.params.get("p2"), Some("5"));
+        assert_eq!(bound.params.get("p3"), Some("25"));
+    }
+
+    #[test]
+    fn test_most_specific_shock_wins() {
+        let req = request(json!({
+            "filters": {"trade_date": "2024-01-15"},
+            "shocks": [
+                {"spot_pct": -5},
+                {"asset_class": "Equity", "spot_pct": -10},
+                {"underlying_symbol": "AAPL", "spot_pct": -20}
+            ]
+        }));
+        let shocks = req.shocks.iter().cloned().map(scoped).collect();
+        let bound = StressQueryBuilder::new(&req, shocks).unwrap().build().unwrap();
+
+        assert!(bound.sql.contains(
+            "multiIf(underlying_symbol = {p1:String}, {p3:Float64}, \
+             asset_class = {p2:String}, {p4:Float64}, 1, {p5:Float64}, 0)"
+        ));
+        assert_eq!(bound.params.get("p1"), Some("AAPL"));
+        assert_eq!(bound.params.get("p3"), Some("-20"));
+    }
+
+    #[test]
+    fn test_row_scenario_shocks() {
+        let req = request(json!({
+            "by_row_scenario": true,
+            "dimensions": ["scenario"],
+            "filters": {"trade_date": "2024-01-15"}
+        }));
+        let shocks = vec![ScopedShock {
+            scenario: Some("Stress".to_string()),
+            shock: Shock { spot_pct: -10.0, ..Default::default() },
+        }];
+        let bound = StressQueryBuilder::new(&req, shocks).unwrap().build().unwrap();
+
+        assert!(bound.sql.contains("multiIf(scenario = {p1:String}, {p2:Float64}, 0)"));
+        assert!(bound.sql.contains("AND exposure_type IN ('Direct', 'ETF', 'ETC')) GROUP BY scenario"));
+    }
+
+    #[test]
+    fn test_invalid_stress_requests_rejected() {
+        let both = request(json!({"shock_set": "Stress", "shocks": [{"spot_pct": -10}]}));
+        assert!(StressQueryBuilder::new(&both, vec![]).is_err());
+
+        let none = request(json!({"filters": {"trade_date": "2024-01-15"}}));
+        assert!(StressQueryBuilder::new(&none, vec![]).is_err());
+
+        // Without a single trade date every day's Greeks would be summed
+        let shocks = vec![scoped(Shock { spot_pct: -10.0, ..Default::default() })];
+        let undated = request(json!({"shocks": [{"spot_pct": -10}]}));
+        assert!(StressQueryBuilder::new(&undated, shocks.clone()).is_err());
+        let ranged = request(json!({
+            "filters": {"trade_date_range": {"start": "2024-01-01", "end": "2024-01-15"}},
+            "shocks": [{"spot_pct": -10}]
+        }));
+        assert!(StressQueryBuilder::new(&ranged, shocks).is_err());
+
+        assert!(validate_shocks(&[Shock { spot_pct: -100.0, ..Default::default() }]).is_err());
+        assert!(validate_shocks(&[Shock { rate_bp: f64::NAN, ..Default::default() }]).is_err());
+        assert!(validate_shocks(&[Shock {
+            asset_class: Some("Equity".to_string()),
+            underlying_symbol: Some("AAPL".to_string()),
+            ..Default::default()
+        }])
+        .is_err());
+    }
+}
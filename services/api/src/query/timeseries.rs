@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::request::{DateRange, PivotFilters, TimeInterval, TimeseriesQuery};
+use crate::models::response::{TimeseriesPoint, TimeseriesSeries};
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+use crate::query::planner::SourceTable;
+use crate::query::predicate::compile_predicate;
+use crate::query::{Dimension, Metric};
+
+/// Alias of the bucket start column.
+pub const BUCKET_ALIAS: &str = "bucket";
+
+/// Alias of the metric column.
+pub const VALUE_ALIAS: &str = "value";
+
+/// Longest span, in days, of an intraday (5-minute or hourly) series.
+const MAX_INTRADAY_DAYS: i64 = 7;
+
+impl TimeInterval {
+    /// Dimension the series is bucketed by.
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            TimeInterval::FiveMinute => Dimension::Ts5m,
+            TimeInterval::Hour => Dimension::TsHour,
+            TimeInterval::Day => Dimension::TradeDate,
+            TimeInterval::Week => Dimension::TradeWeek,
+            TimeInterval::Month => Dimension::TradeMonth,
+            TimeInterval::Quarter => Dimension::TradeQuarter,
+        }
+    }
+
+    /// `WITH FILL` step between consecutive buckets.
+    fn step(&self) -> &'static str {
+        match self {
+            TimeInterval::FiveMinute => "INTERVAL 5 MINUTE",
+            TimeInterval::Hour => "INTERVAL 1 HOUR",
+            TimeInterval::Day => "INTERVAL 1 DAY",
+            TimeInterval::Week => "INTERVAL 1 WEEK",
+            TimeInterval::Month => "INTERVAL 1 MONTH",
+            TimeInterval::Quarter => "INTERVAL 1 QUARTER",
+        }
+    }
+
+    /// Start of the bucket containing the `Date` expression `date`, typed like the bucket column.
+    fn bucket_of(&self, date: &str) -> String {
+        match self {
+            TimeInterval::FiveMinute | TimeInterval::Hour => format!("toDateTime({})", date),
+            TimeInterval::Day => date.to_string(),
+            TimeInterval::Week => format!("toMonday({})", date),
+            TimeInterval::Month => format!("toStartOfMonth({})", date),
+            TimeInterval::Quarter => format!("toStartOfQuarter({})", date),
+        }
+    }
+
+    fn is_intraday(&self) -> bool {
+        matches!(self, TimeInterval::FiveMinute | TimeInterval::Hour)
+    }
+}
+
+/// Gap-filled metric series over time buckets, one per `group_by` combination.
+pub struct TimeseriesQueryBuilder {
+    interval: TimeInterval,
+    group_by: Vec<Dimension>,
+    metric: Metric,
+    filters: PivotFilters,
+    cumulative: bool,
+    source: SourceTable,
+}
+
+impl TimeseriesQueryBuilder {
+    pub fn from_query(query: &TimeseriesQuery) -> Result<Self, ApiError> {
+        let start = query.trade_date.clone();
+        let end = query.end_date.clone().unwrap_or_else(|| start.clone());
+        validate_date("trade_date", &start)?;
+        validate_date("end_date", &end)?;
+
+        let days = date_span(&start, &end);
+        if days < 0 {
+            return Err(ApiError::QueryValidation(
+                "end_date must not be before trade_date".to_string(),
+            ));
+        }
+        if query.interval.is_intraday() && days >= MAX_INTRADAY_DAYS {
+            return Err(ApiError::QueryValidation(format!(
+                "Intraday series are limited to {} days",
+                MAX_INTRADAY_DAYS
+            )));
+        }
+
+        let group_by = Self::parse_group_by(&query.group_by)?;
+        let filters = PivotFilters {
+            trade_date_range: Some(DateRange { start, end }),
+            ..Default::default()
+        };
+
+        let mut dimensions = group_by.clone();
+        dimensions.push(query.interval.dimension());
+        let source = SourceTable::plan(&dimensions, &[query.metric], &filters);
+
+        Ok(Self {
+            interval: query.interval,
+            group_by,
+            metric: query.metric,
+            filters,
+            cumulative: query.cumulative,
+            source,
+        })
+    }
+
+    pub fn source(&self) -> SourceTable {
+        self.source
+    }
+
+    pub fn build(&self) -> BoundQuery {
+        let mut params = QueryParams::new();
+        let range = self
+            .filters
+            .trade_date_range
+            .as_ref()
+            .expect("range is set in from_query");
+        let start = params.bind("Date", &range.start);
+        let end = params.bind("Date", &range.end);
+
+        let group_cols: Vec<&str> = self.group_by.iter().map(|d| d.to_column()).collect();
+        let mut select: Vec<String> = self.group_by.iter().map(|d| d.to_select()).collect();
+        select.push(format!(
+            "{} AS {}",
+            self.interval.dimension().to_expr(),
+            BUCKET_ALIAS
+        ));
+        select.push(format!(
+            "{} AS {}",
+            self.source
+                .aggregation(&self.metric)
+                .unwrap_or_else(|| self.metric.to_aggregation()),
+            VALUE_ALIAS
+        ));
+
+        let where_clauses: Vec<String> = self
+            .filters
+            .to_predicates()
+            .iter()
+            .map(|p| compile_predicate(p, &mut params).expect("dates are validated in from_query"))
+            .collect();
+
+        let mut keys = group_cols.clone();
+        keys.push(BUCKET_ALIAS);
+
+        // Columns before the WITH FILL key form a sorting prefix, so each group is filled
+        // independently over the whole range.
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} GROUP BY {} ORDER BY {} WITH FILL FROM {} TO {} + INTERVAL 1 DAY STEP {}",
+            select.join(", "),
+            self.source.table(),
+            where_clauses.join(" AND "),
+            keys.join(", "),
+            keys.join(", "),
+            self.interval.bucket_of(&start),
+            self.interval.bucket_of(&end),
+            self.interval.step()
+        );
+
+        BoundQuery::new(sql, params)
+    }
+
+    /// Splits ordered result rows into one series per group, accumulating if requested.
+    pub fn into_series(&self, rows: Vec<HashMap<String, Value>>) -> Vec<TimeseriesSeries> {
+        let mut series: Vec<TimeseriesSeries> = Vec::new();
+        let mut running = 0.0;
+
+        for mut row in rows {
+            let groups: HashMap<String, Value> = self
+                .group_by
+                .iter()
+                .map(|d| {
+                    let column = d.to_column();
+                    (column.to_string(), row.remove(column).unwrap_or(Value::Null))
+                })
+                .collect();
+
+            if series.last().is_none_or(|s| s.groups != groups) {
+                series.push(TimeseriesSeries { groups, points: Vec::new() });
+                running = 0.0;
+            }
+
+            let bucket = match row.remove(BUCKET_ALIAS) {
+                Some(Value::String(s)) => s,
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            let mut value = row.get(VALUE_ALIAS).and_then(|v| {
+                v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            });
+            if self.cumulative {
+                running += value.unwrap_or(0.0);
+                value = Some(running);
+            }
+
+            if let Some(current) = series.last_mut() {
+                current.points.push(TimeseriesPoint { bucket, value });
+            }
+        }
+
+        series
+    }
+
+    /// Comma-separated dimensions; time buckets are rejected since the series already has one.
+    fn parse_group_by(group_by: &str) -> Result<Vec<Dimension>, ApiError> {
+        let mut dimensions: Vec<Dimension> = Vec::new();
+
+        for name in group_by.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let dimension = Dimension::all()
+                .iter()
+                .copied()
+                .find(|d| d.to_column() == name)
+                .filter(|d| !matches!(d.clickhouse_type(), "Date" | "DateTime"))
+                .ok_or_else(|| {
+                    ApiError::QueryValidation(format!(
+                        "Invalid group_by column: '{}'. Time buckets are set by interval",
+                        name
+                    ))
+                })?;
+            if dimensions.contains(&dimension) {
+                return Err(ApiError::QueryValidation(format!(
+                    "Duplicate group_by column: '{}'",
+                    name
+                )));
+            }
+            dimensions.push(dimension);
+        }
+
+        Ok(dimensions)
+    }
+}
+
+/// Days from `start` to `end`; both are already validated dates.
+fn date_span(start: &str, end: &str) -> i64 {
+    let parse = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap_or_default();
+    (parse(end) - parse(start)).num_days()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(interval: TimeInterval, group_by: &str) -> TimeseriesQuery {
+        TimeseriesQuery {
+            trade_date: "2024-01-15".to_string(),
+            end_date: None,
+            interval,
+            group_by: group_by.to_string(),
+            metric: Metric::Pnl,
+            cumulative: false,
+            cache_bypass: false,
+        }
+    }
+
+    #[test]
+    fn test_intraday_series_is_gap_filled_per_group() {
+        let builder =
+            TimeseriesQueryBuilder::from_query(&query(TimeInterval::FiveMinute, "desk")).unwrap();
+        let sql = builder.build().sql;
+
+        assert_eq!(
+            sql,
+            "SELECT desk, toStartOfFiveMinutes(toDateTime(ts)) AS bucket, sum(pnl) AS value \
+             FROM pivot.trades_1d \
+             WHERE trade_date >= {p2:Date} AND trade_date <= {p3:Date} \
+             GROUP BY desk, bucket \
+             ORDER BY desk, bucket WITH FILL FROM toDateTime({p0:Date}) \
+             TO toDateTime({p1:Date}) + INTERVAL 1 DAY STEP INTERVAL 5 MINUTE"
+        );
+    }
+
+    #[test]
+    fn test_monthly_series_uses_rollup() {
+        let mut q = query(TimeInterval::Month, "book");
+        q.end_date = Some("2024-06-30".to_string());
+        let builder = TimeseriesQueryBuilder::from_query(&q).unwrap();
+
+        assert_eq!(builder.source(), SourceTable::Rollup);
+        let sql = builder.build().sql;
+        assert!(sql.contains("toStartOfMonth(trade_date) AS bucket, sumMerge(pnl_state) AS value"));
+        assert!(sql.contains("WITH FILL FROM toStartOfMonth({p0:Date})"));
+    }
+
+    #[test]
+    fn test_invalid_timeseries_queries_rejected() {
+        let mut q = query(TimeInterval::Hour, "");
+        q.end_date = Some("2024-02-15".to_string());
+        assert!(TimeseriesQueryBuilder::from_query(&q).is_err());
+
+        q.end_date = Some("2024-01-01".to_string());
+        q.interval = TimeInterval::Day;
+        assert!(TimeseriesQueryBuilder::from_query(&q).is_err());
+
+        assert!(TimeseriesQueryBuilder::from_query(&query(TimeInterval::Day, "ts_hour")).is_err());
+        assert!(TimeseriesQueryBuilder::from_query(&query(TimeInterval::Day, "desk,desk")).is_err());
+        assert!(TimeseriesQueryBuilder::from_query(&query(TimeInterval::Day, "nope")).is_err());
+    }
+
+    #[test]
+    fn test_cumulative_series() {
+        let mut q = query(TimeInterval::Hour, "desk");
+        q.cumulative = true;
+        let builder = TimeseriesQueryBuilder::from_query(&q).unwrap();
+
+        let row = |desk: &str, bucket: &str, value: Value| {
+            HashMap::from([
+                ("desk".to_string(), Value::from(desk)),
+                (BUCKET_ALIAS.to_string(), Value::from(bucket)),
+                (VALUE_ALIAS.to_string(), value),
+            ])
+        };
+        let series = builder.into_series(vec![
+            row("FX", "2024-01-15 09:00:00", Value::from(10.0)),
+            row("FX", "2024-01-15 10:00:00", Value::from(0)),
+            row("FX", "2024-01-15 11:00:00", Value::from(-4.5)),
+            row("Rates", "2024-01-15 09:00:00", Value::from(3.0)),
+        ]);
+
+        assert_eq!(series.len(), 2);
+        let values: Vec<Option<f64>> = series[0].points.iter().map(|p| p.value).collect();
+        assert_eq!(values, vec![Some(10.0), Some(10.0), Some(5.5)]);
+        assert_eq!(series[1].points[0].value, Some(3.0));
+    }
+}
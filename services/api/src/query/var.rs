@@ -0,0 +1,351 @@
+//! Historical-simulation VaR and expected shortfall.
+//!
+//! Net exposure per underlying on `trade_date` (looked through ETF/ETC baskets) is
+//! revalued under each of the last `lookback_days` daily returns in
+//! `pivot.price_history`, giving one P&L scenario per return date. VaR is the loss
+//! at the confidence quantile of those scenarios and ES the mean loss over the
+//! scenarios at or beyond it.
+//!
+//! The exposure is that of the trades booked on `trade_date`, not the holdings
+//! carried into it: positions opened on earlier days are not revalued.
+//!
+//! Exposures are converted into one reporting currency (`USD` unless requested)
+//! before they are netted, so trades in different currencies never sum raw.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::request::VarQuery;
+use crate::models::response::VarGroupRow;
+use crate::query::fx::{converted_source, validate_currency, BASE_CURRENCY};
+use crate::query::lookthrough::LOOK_THROUGH_SOURCE;
+use crate::query::params::{validate_date, BoundQuery, QueryParams};
+
+/// Alias of the scenario date column.
+const SCENARIO_ALIAS: &str = "scenario_date";
+
+/// Alias of the scenario P&L column.
+const PNL_ALIAS: &str = "pnl";
+
+/// Alias of the group's net exposure, repeated on each of its scenario rows.
+const EXPOSURE_ALIAS: &str = "group_exposure";
+
+const MIN_LOOKBACK_DAYS: u32 = 20;
+const MAX_LOOKBACK_DAYS: u32 = 2_500;
+
+pub struct VarQueryBuilder {
+    query: VarQuery,
+}
+
+/// Portfolio VaR and ES with each group's standalone and contributed risk.
+#[derive(Debug)]
+pub struct VarEstimate {
+    pub scenarios: usize,
+    pub var: f64,
+    pub expected_shortfall: f64,
+    pub groups: Vec<VarGroupRow>,
+}
+
+impl VarQueryBuilder {
+    pub fn from_query(query: &VarQuery) -> Result<Self, ApiError> {
+        validate_date("trade_date", &query.trade_date)?;
+
+        if !(query.confidence > 0.5 && query.confidence < 1.0) {
+            return Err(ApiError::QueryValidation(format!(
+                "confidence must be in (0.5, 1), got {}",
+                query.confidence
+            )));
+        }
+        if !(MIN_LOOKBACK_DAYS..=MAX_LOOKBACK_DAYS).contains(&query.lookback_days) {
+            return Err(ApiError::QueryValidation(format!(
+                "lookback_days must be between {} and {}",
+                MIN_LOOKBACK_DAYS, MAX_LOOKBACK_DAYS
+            )));
+        }
+        if let Some(ref currency) = query.reporting_currency {
+            validate_currency(currency)?;
+        }
+        if query.group_by.is_derived() {
+            return Err(ApiError::QueryValidation(format!(
+                "VaR is grouped by a stored dimension, not '{}'",
+                query.group_by.to_column()
+            )));
+        }
+
+        Ok(Self { query: query.clone() })
+    }
+
+    /// Scenario P&L per group and return date.
+    pub fn build(&self) -> BoundQuery {
+        let mut params = QueryParams::new();
+        let trade_date = params.bind("Date", &self.query.trade_date);
+        let mut conditions = vec![format!("trade_date = {}", trade_date)];
+        if let Some(fund_id) = self.query.fund_id {
+            conditions.push(format!("fund_id = {}", params.bind("UInt32", fund_id)));
+        }
+        if let Some(pm_id) = self.query.portfolio_manager_id {
+            conditions.push(format!("portfolio_manager_id = {}", params.bind("UInt32", pm_id)));
+        }
+        if let Some(desk) = &self.query.desk {
+            conditions.push(format!("desk = {}", params.bind("String", desk)));
+        }
+        let group = self.query.group_by.to_column();
+        let source = converted_source(LOOK_THROUGH_SOURCE.as_str(), self.currency(), &mut params);
+
+        // One more price date than returns, so the oldest return has a previous close
+        let sql = format!(
+            "SELECT {group}, toString(r.price_date) AS {scenario}, sum(p.exposure * r.ret) AS {pnl}, \
+             any(p.net_exposure) AS {exposure} \
+             FROM (\
+             SELECT {group}, underlying_symbol, sum(notional) AS exposure, \
+             sum(sum(notional)) OVER (PARTITION BY {group}) AS net_exposure \
+             FROM {source} WHERE {where} \
+             GROUP BY {group}, underlying_symbol\
+             ) AS p \
+             INNER JOIN (\
+             SELECT symbol, price_date, close / prev_close - 1 AS ret FROM (\
+             SELECT symbol, price_date, close, \
+             lagInFrame(close) OVER (PARTITION BY symbol ORDER BY price_date \
+             ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) AS prev_close \
+             FROM pivot.price_history FINAL \
+             WHERE price_date IN (\
+             SELECT DISTINCT price_date FROM pivot.price_history \
+             WHERE price_date <= {trade_date} ORDER BY price_date DESC LIMIT {dates}\
+             )) WHERE prev_close > 0\
+             ) AS r ON r.symbol = p.underlying_symbol \
+             GROUP BY {group}, {scenario} \
+             ORDER BY {group}, {scenario}",
+            group = group,
+            scenario = SCENARIO_ALIAS,
+            pnl = PNL_ALIAS,
+            exposure = EXPOSURE_ALIAS,
+            source = source,
+            where = conditions.join(" AND "),
+            trade_date = trade_date,
+            dates = self.query.lookback_days + 1,
+        );
+
+        BoundQuery::new(sql, params)
+    }
+
+    /// Currency the exposures, and so every VaR figure, are in.
+    pub fn currency(&self) -> &str {
+        self.query.reporting_currency.as_deref().unwrap_or(BASE_CURRENCY)
+    }
+
+    /// VaR figures from the rows of [`Self::build`]. Groups without a return on a
+    /// scenario date take zero P&L in it.
+    pub fn estimate(&self, rows: Vec<HashMap<String, Value>>) -> VarEstimate {
+        let column = self.query.group_by.to_column();
+
+        let number = |v: &Value| v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse().ok()));
+
+        let mut dates: BTreeMap<String, usize> = BTreeMap::new();
+        let mut points = Vec::new();
+        for mut row in rows {
+            let Some(date) = row.remove(SCENARIO_ALIAS).and_then(|v| v.as_str().map(str::to_string))
+            else {
+                continue;
+            };
+            let pnl = row.get(PNL_ALIAS).and_then(number).unwrap_or(0.0);
+            let exposure = row.get(EXPOSURE_ALIAS).and_then(number).unwrap_or(0.0);
+            dates.insert(date.clone(), 0);
+            points.push((row.remove(column).unwrap_or(Value::Null), date, pnl, exposure));
+        }
+        for (i, index) in dates.values_mut().enumerate() {
+            *index = i;
+        }
+
+        let scenarios = dates.len();
+        let mut groups: Vec<(Value, f64, Vec<f64>)> = Vec::new();
+        for (group, date, pnl, exposure) in points {
+            let slot = match groups.iter().position(|(g, _, _)| *g == group) {
+                Some(slot) => slot,
+                None => {
+                    groups.push((group, exposure, vec![0.0; scenarios]));
+                    groups.len() - 1
+                }
+            };
+            groups[slot].2[dates[&date]] += pnl;
+        }
+
+        let mut portfolio = vec![0.0; scenarios];
+        for (_, _, pnls) in &groups {
+            for (total, pnl) in portfolio.iter_mut().zip(pnls) {
+                *total += pnl;
+            }
+        }
+
+        let confidence = self.query.confidence;
+        let tail = tail_scenarios(&portfolio, confidence);
+        let (var, expected_shortfall) = var_es(&portfolio, &tail);
+
+        let mut rows: Vec<VarGroupRow> = groups
+            .into_iter()
+            .map(|(group, exposure, pnls)| {
+                let own_tail = tail_scenarios(&pnls, confidence);
+                let (own_var, own_es) = var_es(&pnls, &own_tail);
+                let without: Vec<f64> = portfolio.iter().zip(&pnls).map(|(p, g)| p - g).collect();
+                let (var_without, _) = var_es(&without, &tail_scenarios(&without, confidence));
+                let component_var = tail.last().map_or(0.0, |&i| -pnls[i]);
+
+                VarGroupRow {
+                    group,
+                    exposure,
+                    var: own_var,
+                    expected_shortfall: own_es,
+                    // Component VaR is exposure times marginal VaR (Euler allocation)
+                    marginal_var: (exposure.abs() > 1e-9).then(|| component_var / exposure),
+                    incremental_var: var - var_without,
+                    component_var,
+                    component_es: mean_loss(&pnls, &tail),
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| b.component_var.total_cmp(&a.component_var));
+
+        VarEstimate {
+            scenarios,
+            var,
+            expected_shortfall,
+            groups: rows,
+        }
+    }
+}
+
+/// Indices of the worst `⌈(1 - confidence) · n⌉` scenarios, worst first. The last
+/// one is the VaR scenario.
+fn tail_scenarios(pnls: &[f64], confidence: f64) -> Vec<usize> {
+    if pnls.is_empty() {
+        return Vec::new();
+    }
+    // Tolerance so e.g. 1% of 100 scenarios is 1, not 2, despite rounding
+    let size = (((1.0 - confidence) * pnls.len() as f64) - 1e-9).ceil().max(1.0) as usize;
+    let mut order: Vec<usize> = (0..pnls.len()).collect();
+    order.sort_by(|&a, &b| pnls[a].total_cmp(&pnls[b]));
+    order.truncate(size);
+    order
+}
+
+/// VaR and ES as positive losses over `tail`.
+fn var_es(pnls: &[f64], tail: &[usize]) -> (f64, f64) {
+    let var = tail.last().map_or(0.0, |&i| -pnls[i]);
+    (var, mean_loss(pnls, tail))
+}
+
+fn mean_loss(pnls: &[f64], tail: &[usize]) -> f64 {
+    if tail.is_empty() {
+        return 0.0;
+    }
+    -tail.iter().map(|&i| pnls[i]).sum::<f64>() / tail.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Dimension;
+    use serde_json::json;
+
+    fn query() -> VarQuery {
+        serde_json::from_value(json!({"trade_date": "2024-01-15", "desk": "FX"})).unwrap()
+    }
+
+    fn row(fund: u32, date: &str, pnl: f64) -> HashMap<String, Value> {
+        HashMap::from([
+            ("fund_id".to_string(), json!(fund)),
+            (SCENARIO_ALIAS.to_string(), json!(date)),
+            (PNL_ALIAS.to_string(), json!(pnl)),
+            (EXPOSURE_ALIAS.to_string(), json!(fund * 1000)),
+        ])
+    }
+
+    #[test]
+    fn test_var_query() {
+        let bound = VarQueryBuilder::from_query(&query()).unwrap().build();
+
+        assert!(bound.sql.starts_with(
+            "SELECT fund_id, toString(r.price_date) AS scenario_date, sum(p.exposure * r.ret) AS pnl"
+        ));
+        assert!(bound.sql.contains("WHERE trade_date = {p0:Date} AND desk = {p1:String} GROUP BY fund_id, underlying_symbol"));
+        assert!(bound.sql.contains("WHERE price_date <= {p0:Date} ORDER BY price_date DESC LIMIT 251"));
+        assert!(bound.sql.contains("ON r.symbol = p.underlying_symbol"));
+        assert!(bound.sql.contains("any(p.net_exposure) AS group_exposure"));
+        assert!(bound.sql.contains("sum(sum(notional)) OVER (PARTITION BY fund_id) AS net_exposure"));
+        assert_eq!(bound.params.get("p1"), Some("FX"));
+        // Exposures are netted in USD unless another currency is requested
+        assert!(bound.sql.contains("{p2:String} AS currency) FROM (SELECT t.*"));
+        assert_eq!(bound.params.get("p2"), Some("USD"));
+
+        let mut q = query();
+        q.reporting_currency = Some("EUR".to_string());
+        let bound = VarQueryBuilder::from_query(&q).unwrap().build();
+        assert_eq!(bound.params.get("p2"), Some("EUR"));
+    }
+
+    #[test]
+    fn test_var_and_components() {
+        let mut q = query();
+        q.confidence = 0.75;
+        let builder = VarQueryBuilder::from_query(&q).unwrap();
+
+        // Fund 1 loses on d1, fund 2 on d2; the portfolio's worst day is d1.
+        let rows = vec![
+            row(1, "d1", -100.0),
+            row(1, "d2", 10.0),
+            row(1, "d3", 20.0),
+            row(1, "d4", 30.0),
+            row(2, "d1", -20.0),
+            row(2, "d2", -60.0),
+            row(2, "d3", 5.0),
+        ];
+        let estimate = builder.estimate(rows);
+
+        assert_eq!(estimate.scenarios, 4);
+        assert_eq!(estimate.var, 120.0);
+        assert_eq!(estimate.expected_shortfall, 120.0);
+
+        let fund1 = &estimate.groups[0];
+        assert_eq!(fund1.group, json!(1));
+        assert_eq!(fund1.var, 100.0);
+        assert_eq!(fund1.component_var, 100.0);
+        // Without fund 1 the worst day is d2 at -60
+        assert_eq!(fund1.incremental_var, 60.0);
+        assert_eq!(fund1.exposure, 1000.0);
+        assert_eq!(fund1.marginal_var, Some(0.1));
+
+        let fund2 = &estimate.groups[1];
+        assert_eq!(fund2.var, 60.0);
+        assert_eq!(fund2.component_var, 20.0);
+        let total: f64 = estimate.groups.iter().map(|g| g.component_var).sum();
+        assert_eq!(total, estimate.var);
+    }
+
+    #[test]
+    fn test_tail_size() {
+        let pnls: Vec<f64> = (0..100).map(f64::from).collect();
+        assert_eq!(tail_scenarios(&pnls, 0.99), vec![0]);
+        assert_eq!(tail_scenarios(&pnls, 0.975), vec![0, 1, 2]);
+        assert!(tail_scenarios(&[], 0.99).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_var_queries_rejected() {
+        let mut q = query();
+        q.confidence = 1.0;
+        assert!(VarQueryBuilder::from_query(&q).is_err());
+
+        q.confidence = 0.95;
+        q.lookback_days = 5;
+        assert!(VarQueryBuilder::from_query(&q).is_err());
+
+        q.lookback_days = 250;
+        q.group_by = Dimension::TradeMonth;
+        assert!(VarQueryBuilder::from_query(&q).is_err());
+
+        q.group_by = Dimension::FundId;
+        q.reporting_currency = Some("usd".to_string());
+        assert!(VarQueryBuilder::from_query(&q).is_err());
+    }
+}
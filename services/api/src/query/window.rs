@@ -0,0 +1,144 @@
+//! Window metrics: running totals, period-to-date sums and rank within parent.
+//!
+//! Each is computed over an already-aggregated metric, e.g. the month-to-date P&L of
+//! a row is `sum(sum(pnl)) OVER (PARTITION BY <group>, toStartOfMonth(trade_date)
+//! ORDER BY trade_date ...)`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::query::Metric;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowKind {
+    /// Running total along `trade_date` over the requested range.
+    Running,
+    /// Month-to-date total as of each `trade_date`.
+    Mtd,
+    /// Year-to-date total as of each `trade_date`.
+    Ytd,
+    /// Rank (1 = largest) among siblings sharing the same parent dimensions.
+    Rank,
+}
+
+impl WindowKind {
+    /// Start-of-period function for period-to-date kinds.
+    pub fn period_start(&self) -> Option<&'static str> {
+        match self {
+            WindowKind::Mtd => Some("toStartOfMonth"),
+            WindowKind::Ytd => Some("toStartOfYear"),
+            WindowKind::Running | WindowKind::Rank => None,
+        }
+    }
+
+    /// Whether the window accumulates along `trade_date`.
+    pub fn is_cumulative(&self) -> bool {
+        !matches!(self, WindowKind::Rank)
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            WindowKind::Running => "running",
+            WindowKind::Mtd => "mtd",
+            WindowKind::Ytd => "ytd",
+            WindowKind::Rank => "rank",
+        }
+    }
+
+    /// Parses a kind from its name, as used in query strings.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [WindowKind::Running, WindowKind::Mtd, WindowKind::Ytd, WindowKind::Rank]
+            .into_iter()
+            .find(|k| k.suffix() == name)
+    }
+}
+
+/// A window applied to a metric, e.g. `{"kind": "mtd", "metric": "pnl"}`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WindowMetric {
+    pub kind: WindowKind,
+    pub metric: Metric,
+}
+
+impl WindowMetric {
+    /// Column alias, e.g. `total_pnl_mtd`.
+    pub fn alias(&self) -> String {
+        format!("{}_{}", self.metric.alias(), self.kind.suffix())
+    }
+
+    /// Window expression over `aggregate`, the metric's aggregate in the enclosing `GROUP BY`.
+    ///
+    /// Cumulative kinds are partitioned by `partition` (the non-time dimensions) and ordered
+    /// by `time`; rank is partitioned by `partition` (the parent dimensions).
+    pub fn to_sql(&self, aggregate: &str, partition: &[&str], time: &str) -> String {
+        let mut partition: Vec<String> = partition.iter().map(|p| p.to_string()).collect();
+
+        let (function, order) = match self.kind {
+            WindowKind::Rank => ("rank()".to_string(), format!("{} DESC", aggregate)),
+            kind => {
+                if let Some(start) = kind.period_start() {
+                    partition.push(format!("{}({})", start, time));
+                }
+                (
+                    format!("sum({})", aggregate),
+                    format!("{} ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW", time),
+                )
+            }
+        };
+
+        let partition = if partition.is_empty() {
+            String::new()
+        } else {
+            format!("PARTITION BY {} ", partition.join(", "))
+        };
+        format!("{} OVER ({}ORDER BY {})", function, partition, order)
+    }
+}
+
+/// Earliest date the windows need as of `start`: the start of the year for YTD, of the month
+/// for MTD, otherwise `start` itself.
+pub fn lookback_start(windows: &[WindowMetric], start: &str) -> String {
+    let kinds: Vec<WindowKind> = windows.iter().map(|w| w.kind).collect();
+    let period = if kinds.contains(&WindowKind::Ytd) {
+        WindowKind::Ytd.period_start()
+    } else if kinds.contains(&WindowKind::Mtd) {
+        WindowKind::Mtd.period_start()
+    } else {
+        None
+    };
+
+    match period {
+        Some(function) => format!("{}({})", function, start),
+        None => start.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_sql() {
+        let mtd = WindowMetric { kind: WindowKind::Mtd, metric: Metric::Pnl };
+        assert_eq!(mtd.alias(), "total_pnl_mtd");
+        assert_eq!(
+            mtd.to_sql("sum(pnl)", &["desk"], "trade_date"),
+            "sum(sum(pnl)) OVER (PARTITION BY desk, toStartOfMonth(trade_date) \
+             ORDER BY trade_date ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)"
+        );
+
+        let rank = WindowMetric { kind: WindowKind::Rank, metric: Metric::Notional };
+        assert_eq!(
+            rank.to_sql("sum(notional)", &[], "trade_date"),
+            "rank() OVER (ORDER BY sum(notional) DESC)"
+        );
+    }
+
+    #[test]
+    fn test_lookback_start() {
+        let running = WindowMetric { kind: WindowKind::Running, metric: Metric::Pnl };
+        let ytd = WindowMetric { kind: WindowKind::Ytd, metric: Metric::Pnl };
+        assert_eq!(lookback_start(&[running], "{p0:Date}"), "{p0:Date}");
+        assert_eq!(lookback_start(&[running, ytd], "{p0:Date}"), "toStartOfYear({p0:Date})");
+    }
+}
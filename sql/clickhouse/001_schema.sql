@@ -62,6 +62,7 @@ CREATE TABLE IF NOT EXISTS pivot.trades_1d
     underlying_symbol String,              -- The actual underlying (AAPL, GOLD, etc.)
     parent_symbol String DEFAULT '',       -- For constituents: the parent ETF/ETC
     exposure_type LowCardinality(String),  -- 'Direct', 'ETF', 'ETC', 'Constituent'
+    lookthrough_path String DEFAULT '',    -- For constituents: chain from the traded composite, e.g. 'PMET → GLD → GOLD'
     currency LowCardinality(String),
     counterparty String,
 
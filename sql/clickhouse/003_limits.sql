@@ -0,0 +1,18 @@
+-- Risk limits evaluated by /api/v1/limits/breaches.
+-- Every write inserts a new version; the latest per limit_id wins and
+-- deletes are tombstones with is_deleted = 1.
+CREATE TABLE IF NOT EXISTS pivot.risk_limits
+(
+    limit_id String,
+    name String,
+    kind LowCardinality(String),        -- 'metric' or 'concentration'
+    dimension LowCardinality(String),   -- Group the limit applies per, e.g. 'desk', 'fund_id'
+    scope_value String DEFAULT '',      -- Only this group when set; every group otherwise
+    metric LowCardinality(String) DEFAULT '',  -- Pivot metric for 'metric' limits
+    absolute UInt8 DEFAULT 0,           -- Compare |value| rather than value
+    max_value Float64,                  -- Metric cap, or percent of gross for 'concentration'
+    updated_at DateTime64(3) DEFAULT now64(3),
+    is_deleted UInt8 DEFAULT 0
+)
+ENGINE = ReplacingMergeTree(updated_at, is_deleted)
+ORDER BY limit_id;
@@ -0,0 +1,13 @@
+-- Daily FX rates used to convert amounts into a reporting currency.
+-- Rates are quoted against USD; other pairs are crossed through it.
+CREATE TABLE IF NOT EXISTS pivot.fx_rates
+(
+    rate_date Date,
+    base_currency LowCardinality(String),
+    quote_currency LowCardinality(String),
+    rate Float64,                  -- Units of quote_currency per unit of base_currency
+    created_at DateTime DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(created_at)
+PARTITION BY toYYYYMM(rate_date)
+ORDER BY (rate_date, base_currency, quote_currency);
@@ -0,0 +1,21 @@
+-- Named shock sets applied by /api/v1/stress. A set named after a value of
+-- trades_1d.scenario defines what that scenario means.
+-- Every write inserts a new version; the latest per name wins.
+CREATE TABLE IF NOT EXISTS pivot.shock_sets
+(
+    name String,
+    description String DEFAULT '',
+    shocks String,                      -- JSON array of shocks
+    updated_at DateTime64(3) DEFAULT now64(3)
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY name;
+
+-- Default sets for the generated scenarios. They are stamped at the epoch so
+-- re-running this file never overrides sets edited through the API.
+INSERT INTO pivot.shock_sets (name, description, shocks, updated_at) VALUES
+    ('Base', 'No market move', '[]', 0),
+    ('Stress', 'Spot -10%, vol +5 points, rates +25bp',
+     '[{"spot_pct": -10, "vol_points": 5, "rate_bp": 25}]', 0),
+    ('Historical', 'Equity sell-off with a flight to commodities and a rates rally',
+     '[{"spot_pct": -5, "vol_points": 3, "rate_bp": -50}, {"asset_class": "Equity", "spot_pct": -20, "vol_points": 15, "rate_bp": -50}, {"asset_class": "Commodity", "spot_pct": 5, "vol_points": 5, "rate_bp": -50}]', 0);
@@ -0,0 +1,12 @@
+-- Daily closing prices used by /api/v1/var for historical simulation.
+-- Covers traded instruments and ETF/ETC constituents, in the instrument currency.
+CREATE TABLE IF NOT EXISTS pivot.price_history
+(
+    price_date Date,
+    symbol LowCardinality(String),
+    close Float64,
+    created_at DateTime DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(created_at)
+PARTITION BY toYYYYMM(price_date)
+ORDER BY (symbol, price_date);
@@ -0,0 +1,55 @@
+-- Daily buy and sell legs per account, portfolio and traded symbol, read by
+-- /api/v1/positions to build running positions as of a date.
+-- quantity and notional are signed in trades_1d: negative for sells.
+-- Constituent rows are skipped; positions are held in the traded instrument.
+CREATE TABLE IF NOT EXISTS pivot.positions_1d
+(
+    trade_date Date,
+    fund_id UInt32,
+    account_id UInt64,
+    portfolio_manager_id UInt32,
+    portfolio_id UInt64,
+    symbol String,
+    asset_class LowCardinality(String),
+    currency LowCardinality(String),
+    buy_quantity_state AggregateFunction(sum, Float64),
+    buy_notional_state AggregateFunction(sum, Float64),
+    sell_quantity_state AggregateFunction(sum, Float64),   -- Positive quantity sold
+    sell_notional_state AggregateFunction(sum, Float64),   -- Positive notional sold
+    last_price_state AggregateFunction(argMax, Float64, DateTime64(3))
+)
+ENGINE = AggregatingMergeTree
+PARTITION BY toYYYYMM(trade_date)
+ORDER BY (fund_id, account_id, portfolio_manager_id, portfolio_id, symbol, asset_class, currency, trade_date);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS pivot.positions_1d_mv
+TO pivot.positions_1d
+AS
+SELECT
+    trade_date,
+    fund_id,
+    account_id,
+    portfolio_manager_id,
+    portfolio_id,
+    symbol,
+    asset_class,
+    currency,
+    sumStateIf(quantity, quantity > 0) AS buy_quantity_state,
+    sumStateIf(notional, quantity > 0) AS buy_notional_state,
+    sumStateIf(-quantity, quantity < 0) AS sell_quantity_state,
+    sumStateIf(-notional, quantity < 0) AS sell_notional_state,
+    argMaxState(price, ts) AS last_price_state
+FROM pivot.trades_1d
+WHERE exposure_type != 'Constituent'
+GROUP BY
+    trade_date,
+    fund_id,
+    account_id,
+    portfolio_manager_id,
+    portfolio_id,
+    symbol,
+    asset_class,
+    currency;
+
+-- The view only sees new inserts. To backfill trades loaded before it existed, run once:
+-- INSERT INTO pivot.positions_1d SELECT ... (the SELECT above) FROM pivot.trades_1d;
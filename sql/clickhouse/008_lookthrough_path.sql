@@ -0,0 +1,4 @@
+-- Adds lookthrough_path to trades_1d tables created before it was part of 001_schema.sql.
+-- The column sits right after exposure_type, where the generator's CSV expects it.
+ALTER TABLE pivot.trades_1d
+    ADD COLUMN IF NOT EXISTS lookthrough_path String DEFAULT '' AFTER exposure_type;
@@ -1,4 +1,4 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
 use clap::Parser;
 use csv::Writer;
 use rand::rngs::StdRng;
@@ -45,6 +45,18 @@ pub struct Args {
     /// Explode ETF/ETC trades into constituent exposures
     #[arg(long, default_value_t = false)]
     pub explode_constituents: bool,
+
+    /// Output file path for FX rates against USD on the trade date
+    #[arg(long)]
+    pub fx_rates_output: Option<PathBuf>,
+
+    /// Output file path for daily closing prices up to the trade date
+    #[arg(long)]
+    pub price_history_output: Option<PathBuf>,
+
+    /// Number of business days of closing prices to generate
+    #[arg(long, default_value_t = 260)]
+    pub price_history_days: usize,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -68,6 +80,21 @@ pub struct Constituent {
     pub effective_date: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct FxRate {
+    pub rate_date: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceClose {
+    pub price_date: String,
+    pub symbol: String,
+    pub close: f64,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct TradeRecord {
     pub trade_date: String,
@@ -89,6 +116,7 @@ pub struct TradeRecord {
     pub underlying_symbol: String,       // For constituents: the actual underlying (AAPL, GOLD, etc.)
     pub parent_symbol: String,           // For constituents: the parent ETF/ETC (SPY, GLD, etc.)
     pub exposure_type: String,           // "Direct", "ETF", "ETC", "Constituent"
+    pub lookthrough_path: String,        // For constituents: chain from the traded composite (PMET → GLD → GOLD)
     pub currency: String,
     pub counterparty: String,
     pub risk_bucket: String,
@@ -146,6 +174,13 @@ fn build_instruments() -> Vec<Instrument> {
         Instrument { symbol: "BAC".into(), name: "Bank of America".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "USD".into(), exchange: "NYSE".into(), sector: "Financials".into(), is_composite: 0 },
         Instrument { symbol: "PG".into(), name: "Procter & Gamble".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "USD".into(), exchange: "NYSE".into(), sector: "Consumer".into(), is_composite: 0 },
         Instrument { symbol: "HD".into(), name: "Home Depot".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "USD".into(), exchange: "NYSE".into(), sector: "Consumer".into(), is_composite: 0 },
+        // Non-USD listings
+        Instrument { symbol: "SAP".into(), name: "SAP SE".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "EUR".into(), exchange: "XETRA".into(), sector: "Technology".into(), is_composite: 0 },
+        Instrument { symbol: "ASML".into(), name: "ASML Holding".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "EUR".into(), exchange: "EURONEXT".into(), sector: "Technology".into(), is_composite: 0 },
+        Instrument { symbol: "HSBA".into(), name: "HSBC Holdings".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "GBP".into(), exchange: "LSE".into(), sector: "Financials".into(), is_composite: 0 },
+        Instrument { symbol: "SHEL".into(), name: "Shell plc".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "GBP".into(), exchange: "LSE".into(), sector: "Energy".into(), is_composite: 0 },
+        Instrument { symbol: "7203".into(), name: "Toyota Motor".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "JPY".into(), exchange: "TSE".into(), sector: "Consumer".into(), is_composite: 0 },
+        Instrument { symbol: "NESN".into(), name: "Nestle SA".into(), asset_class: "Equity".into(), instrument_type: "Stock".into(), currency: "CHF".into(), exchange: "SIX".into(), sector: "Consumer".into(), is_composite: 0 },
         // Commodities for ETCs
         Instrument { symbol: "GOLD".into(), name: "Gold Spot".into(), asset_class: "Commodity".into(), instrument_type: "Commodity".into(), currency: "USD".into(), exchange: "COMEX".into(), sector: "Precious Metals".into(), is_composite: 0 },
         Instrument { symbol: "SILVER".into(), name: "Silver Spot".into(), asset_class: "Commodity".into(), instrument_type: "Commodity".into(), currency: "USD".into(), exchange: "COMEX".into(), sector: "Precious Metals".into(), is_composite: 0 },
@@ -166,6 +201,89 @@ fn build_instruments() -> Vec<Instrument> {
     ]
 }
 
+// USD value of one unit of each instrument currency
+const USD_RATES: &[(&str, f64)] = &[
+    ("USD", 1.0),
+    ("EUR", 1.095),
+    ("GBP", 1.27),
+    ("JPY", 0.0068),
+    ("CHF", 1.17),
+];
+
+/// One rate per non-USD currency of `instruments`, quoted against USD.
+fn build_fx_rates(instruments: &[Instrument], rate_date: &str) -> Vec<FxRate> {
+    let mut currencies: Vec<&str> = instruments
+        .iter()
+        .map(|i| i.currency.as_str())
+        .filter(|c| *c != "USD")
+        .collect();
+    currencies.sort_unstable();
+    currencies.dedup();
+
+    currencies
+        .into_iter()
+        .map(|currency| FxRate {
+            rate_date: rate_date.into(),
+            base_currency: currency.into(),
+            quote_currency: "USD".into(),
+            rate: USD_RATES
+                .iter()
+                .find(|(c, _)| *c == currency)
+                .map(|(_, rate)| *rate)
+                .expect("every instrument currency has a USD rate"),
+        })
+        .collect()
+}
+
+/// Last `days` business days up to and including `end_date`, oldest first.
+fn business_days(end_date: NaiveDate, days: usize) -> Vec<NaiveDate> {
+    let mut dates = Vec::with_capacity(days);
+    let mut date = end_date;
+    while dates.len() < days {
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            dates.push(date);
+        }
+        date = date.pred_opt().expect("date in range");
+    }
+    dates.reverse();
+    dates
+}
+
+/// Standard normal draw (Box-Muller).
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Daily closes for `symbols`, ending on `end_date`. Each symbol follows a
+/// random walk driven by a shared market factor plus its own noise, so the
+/// returns are correlated the way a historical VaR would expect.
+fn build_price_history(symbols: &[String], end_date: NaiveDate, days: usize, seed: u64) -> Vec<PriceClose> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let dates = business_days(end_date, days);
+
+    let mut closes: Vec<f64> = symbols.iter().map(|_| rng.gen_range(10.0..500.0)).collect();
+    let betas: Vec<f64> = symbols.iter().map(|_| rng.gen_range(0.5..1.5)).collect();
+
+    let mut prices = Vec::with_capacity(dates.len() * symbols.len());
+    for (i, date) in dates.iter().enumerate() {
+        let market = 0.01 * standard_normal(&mut rng);
+        for (s, symbol) in symbols.iter().enumerate() {
+            if i > 0 {
+                let ret = betas[s] * market + 0.015 * standard_normal(&mut rng);
+                closes[s] *= (1.0 + ret).max(0.5);
+            }
+            prices.push(PriceClose {
+                price_date: date.format("%Y-%m-%d").to_string(),
+                symbol: symbol.clone(),
+                close: closes[s],
+            });
+        }
+    }
+    prices
+}
+
 // Constituent mappings
 fn build_constituents(effective_date: &str) -> Vec<Constituent> {
     vec![
@@ -199,21 +317,103 @@ fn build_constituents(effective_date: &str) -> Vec<Constituent> {
         // USO - Oil ETC
         Constituent { parent_symbol: "USO".into(), constituent_symbol: "CRUDEOIL".into(), weight: 1.0, shares_per_unit: 0.80, effective_date: effective_date.into() },
         // PMET - Precious Metals Basket
-        Constituent { parent_symbol: "PMET".into(), constituent_symbol: "GOLD".into(), weight: 0.50, shares_per_unit: 0.05, effective_date: effective_date.into() },
-        Constituent { parent_symbol: "PMET".into(), constituent_symbol: "SILVER".into(), weight: 0.30, shares_per_unit: 0.50, effective_date: effective_date.into() },
+        // PMET holds the GLD and SLV ETCs rather than the metals directly
+        Constituent { parent_symbol: "PMET".into(), constituent_symbol: "GLD".into(), weight: 0.50, shares_per_unit: 0.54, effective_date: effective_date.into() },
+        Constituent { parent_symbol: "PMET".into(), constituent_symbol: "SLV".into(), weight: 0.30, shares_per_unit: 0.54, effective_date: effective_date.into() },
         Constituent { parent_symbol: "PMET".into(), constituent_symbol: "PLAT".into(), weight: 0.20, shares_per_unit: 0.02, effective_date: effective_date.into() },
     ]
 }
 
+/// Separator between symbols in `lookthrough_path`.
+const PATH_SEPARATOR: &str = " → ";
+
+/// Leaf exposure of a composite, reached through one or more nested baskets.
+#[derive(Debug, Clone)]
+pub struct LookThroughLeg {
+    pub symbol: String,
+    /// Product of the weights along the path.
+    pub weight: f64,
+    /// Product of the shares per unit along the path.
+    pub shares_per_unit: f64,
+    /// Symbols from the traded composite down to the leaf.
+    pub path: Vec<String>,
+}
+
+/// Resolves `parent` to its leaf constituents, following nested composites.
+///
+/// A constituent already on the path would form a cycle; it is not followed, and
+/// its weight stays on the traded composite as the residual does in query-time
+/// look-through.
+pub fn resolve_look_through(
+    constituent_map: &HashMap<String, Vec<Constituent>>,
+    parent: &str,
+) -> Vec<LookThroughLeg> {
+    let mut legs = Vec::new();
+    let mut path = vec![parent.to_string()];
+    collect_legs(constituent_map, 1.0, 1.0, &mut path, &mut legs);
+    legs
+}
+
+fn collect_legs(
+    constituent_map: &HashMap<String, Vec<Constituent>>,
+    weight: f64,
+    shares_per_unit: f64,
+    path: &mut Vec<String>,
+    legs: &mut Vec<LookThroughLeg>,
+) {
+    let parent = path.last().cloned().unwrap_or_default();
+    for c in constituent_map.get(&parent).into_iter().flatten() {
+        if path.contains(&c.constituent_symbol) {
+            eprintln!(
+                "Warning: keeping constituent cycle {}{}{} on {}",
+                path.join(PATH_SEPARATOR),
+                PATH_SEPARATOR,
+                c.constituent_symbol,
+                path[0]
+            );
+            let (weight, shares_per_unit) = (weight * c.weight, shares_per_unit * c.shares_per_unit);
+            match legs.iter_mut().find(|l| l.symbol == path[0]) {
+                Some(residual) => {
+                    residual.weight += weight;
+                    residual.shares_per_unit += shares_per_unit;
+                }
+                None => legs.push(LookThroughLeg {
+                    symbol: path[0].clone(),
+                    weight,
+                    shares_per_unit,
+                    path: vec![path[0].clone()],
+                }),
+            }
+            continue;
+        }
+
+        path.push(c.constituent_symbol.clone());
+        let weight = weight * c.weight;
+        let shares_per_unit = shares_per_unit * c.shares_per_unit;
+        if constituent_map.contains_key(&c.constituent_symbol) {
+            collect_legs(constituent_map, weight, shares_per_unit, path, legs);
+        } else {
+            legs.push(LookThroughLeg {
+                symbol: c.constituent_symbol.clone(),
+                weight,
+                shares_per_unit,
+                path: path.clone(),
+            });
+        }
+        path.pop();
+    }
+}
+
 pub struct DataGenerator {
     rng: StdRng,
+    seed: u64,
     trade_date: NaiveDate,
     portfolio_managers: u32,
     trade_counter: u64,
     order_counter: u64,
     instruments: Vec<Instrument>,
     constituents: Vec<Constituent>,
-    constituent_map: HashMap<String, Vec<Constituent>>,
+    look_through: HashMap<String, Vec<LookThroughLeg>>,
     explode_constituents: bool,
 }
 
@@ -228,6 +428,8 @@ const COUNTERPARTIES: &[&str] = &[
 ];
 const RISK_BUCKETS: &[&str] = &["Low", "Medium", "High", "VeryHigh"];
 const SCENARIOS: &[&str] = &["Base", "Stress", "Historical", "MonteCarlo"];
+// Share of trades that are sells (negative quantity and notional)
+const SELL_PROBABILITY: f64 = 0.4;
 
 impl DataGenerator {
     pub fn new(seed: u64, trade_date: NaiveDate, portfolio_managers: u32, explode_constituents: bool) -> Self {
@@ -241,16 +443,21 @@ impl DataGenerator {
                 .or_default()
                 .push(c.clone());
         }
+        let look_through = constituent_map
+            .keys()
+            .map(|parent| (parent.clone(), resolve_look_through(&constituent_map, parent)))
+            .collect();
 
         Self {
             rng: StdRng::seed_from_u64(seed),
+            seed,
             trade_date,
             portfolio_managers,
             trade_counter: 0,
             order_counter: 0,
             instruments,
             constituents,
-            constituent_map,
+            look_through,
             explode_constituents,
         }
     }
@@ -263,6 +470,25 @@ impl DataGenerator {
         &self.constituents
     }
 
+    pub fn fx_rates(&self) -> Vec<FxRate> {
+        build_fx_rates(&self.instruments, &self.trade_date.format("%Y-%m-%d").to_string())
+    }
+
+    /// Closes for every instrument and constituent symbol over the `days` business
+    /// days ending on the trade date.
+    pub fn price_history(&self, days: usize) -> Vec<PriceClose> {
+        let mut symbols: Vec<String> = self
+            .instruments
+            .iter()
+            .map(|i| i.symbol.clone())
+            .chain(self.constituents.iter().map(|c| c.constituent_symbol.clone()))
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        build_price_history(&symbols, self.trade_date, days, self.seed)
+    }
+
     fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
         items[self.rng.gen_range(0..items.len())]
     }
@@ -309,7 +535,8 @@ impl DataGenerator {
         let instrument = self.pick_instrument().clone();
         let pm_id = self.rng.gen_range(1..=self.portfolio_managers);
         let fund_id = self.rng.gen_range(1..=50);
-        let quantity = self.rng.gen_range(100.0..10000.0);
+        let side = if self.rng.gen_bool(SELL_PROBABILITY) { -1.0 } else { 1.0 };
+        let quantity = side * self.rng.gen_range(100.0..10000.0);
         let price = self.rng.gen_range(10.0..500.0);
         let notional = quantity * price;
         let pnl = self.rng.gen_range(-50000.0..50000.0);
@@ -351,6 +578,7 @@ impl DataGenerator {
             underlying_symbol: instrument.symbol.clone(), // For direct/ETF/ETC, symbol is the underlying
             parent_symbol: String::new(),                 // No parent for top-level trades
             exposure_type,
+            lookthrough_path: String::new(),
             currency: instrument.currency.clone(),
             counterparty: counterparty.clone(),
             risk_bucket: risk_bucket.clone(),
@@ -366,8 +594,8 @@ impl DataGenerator {
             vega: self.rng.gen_range(-100.0..100.0),
             theta: self.rng.gen_range(-50.0..0.0),
             rho: self.rng.gen_range(-10.0..10.0),
-            margin: notional * self.rng.gen_range(0.05..0.20),
-            fees: notional * self.rng.gen_range(0.0001..0.001),
+            margin: notional.abs() * self.rng.gen_range(0.05..0.20),
+            fees: notional.abs() * self.rng.gen_range(0.0001..0.001),
             slippage: self.rng.gen_range(0.0..0.005),
             vol: self.rng.gen_range(0.1..0.8),
             rate: self.rng.gen_range(0.01..0.05),
@@ -393,9 +621,9 @@ impl DataGenerator {
 
         // If this is a composite instrument and we're exploding constituents
         if self.explode_constituents && instrument.is_composite == 1 {
-            let constituents = self.constituent_map.get(&instrument.symbol).cloned();
-            if let Some(constituents) = constituents {
-                for c in &constituents {
+            let legs = self.look_through.get(&instrument.symbol).cloned();
+            if let Some(legs) = legs {
+                for c in &legs {
                     let constituent_qty = quantity * c.shares_per_unit;
                     let constituent_notional = notional * c.weight;
                     let constituent_pnl = pnl * c.weight;
@@ -418,9 +646,10 @@ impl DataGenerator {
                         product: "Constituent".into(),
                         instrument_type: "Constituent".into(),
                         symbol: instrument.symbol.clone(),           // Keep parent symbol for grouping
-                        underlying_symbol: c.constituent_symbol.clone(), // The actual underlying
+                        underlying_symbol: c.symbol.clone(),         // The actual underlying
                         parent_symbol: instrument.symbol.clone(),    // Link back to parent ETF/ETC
                         exposure_type: "Constituent".to_string(),    // Mark as constituent exposure
+                        lookthrough_path: c.path.join(PATH_SEPARATOR),
                         currency: base_record.currency.clone(),
                         counterparty: counterparty.clone(),
                         risk_bucket: risk_bucket.clone(),
@@ -502,6 +731,26 @@ pub fn generate_to_file(args: &Args) -> Result<GenerationResult, Box<dyn std::er
         csv_writer.flush()?;
     }
 
+    // Generate FX rates file if requested
+    if let Some(path) = &args.fx_rates_output {
+        let file = File::create(path)?;
+        let mut csv_writer = Writer::from_writer(file);
+        for rate in generator.fx_rates() {
+            csv_writer.serialize(rate)?;
+        }
+        csv_writer.flush()?;
+    }
+
+    // Generate price history file if requested
+    if let Some(path) = &args.price_history_output {
+        let file = File::create(path)?;
+        let mut csv_writer = Writer::from_writer(file);
+        for close in generator.price_history(args.price_history_days) {
+            csv_writer.serialize(close)?;
+        }
+        csv_writer.flush()?;
+    }
+
     // Generate trades
     match &args.output {
         Some(path) => {
@@ -587,10 +836,25 @@ mod tests {
         let record = &records[0];
         assert_eq!(record.trade_date, "2024-01-15");
         assert_eq!(record.portfolio_manager_id, 1);
-        assert!(record.quantity > 0.0);
+        assert!(record.quantity.abs() >= 100.0);
         assert!(record.price > 0.0);
     }
 
+    #[test]
+    fn test_sells_have_negative_quantity_and_notional() {
+        let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let mut generator = DataGenerator::new(42, trade_date, 1, true);
+        let records: Vec<TradeRecord> = (0..200).flat_map(|_| generator.generate_records()).collect();
+
+        let sells = records.iter().filter(|r| r.exposure_type != "Constituent" && r.quantity < 0.0).count();
+        assert!(sells > 0 && sells < 200);
+        for record in &records {
+            assert_eq!(record.quantity.signum(), record.notional.signum());
+            // Costs are charged on the traded amount, whichever the side
+            assert!(record.margin >= 0.0 && record.fees >= 0.0);
+        }
+    }
+
     #[test]
     fn test_generate_20_rows_1_pm_50_columns() {
         let args = Args {
@@ -602,6 +866,9 @@ mod tests {
             seed: 42,
             trade_date: "2024-01-15".to_string(),
             explode_constituents: false,
+            fx_rates_output: None,
+            price_history_output: None,
+            price_history_days: 260,
         };
 
         let mut buffer = Cursor::new(Vec::new());
@@ -625,9 +892,9 @@ mod tests {
         // Header + 20 data rows
         assert_eq!(lines.len(), 21, "Expected 21 lines (1 header + 20 rows)");
 
-        // Verify column count in header (55 columns with exposure_type support)
+        // Verify column count in header (56 columns with exposure_type and lookthrough_path)
         let header_cols: Vec<&str> = lines[0].split(',').collect();
-        assert_eq!(header_cols.len(), 55, "Expected 55 columns");
+        assert_eq!(header_cols.len(), 56, "Expected 56 columns");
 
         // Verify all rows have portfolio_manager_id = 1
         for line in &lines[1..] {
@@ -663,6 +930,46 @@ mod tests {
         assert!(found_constituents, "Should have found at least one composite instrument");
     }
 
+    #[test]
+    fn test_nested_look_through() {
+        let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let generator = DataGenerator::new(42, trade_date, 1, true);
+
+        let legs = &generator.look_through["PMET"];
+        let gold = legs.iter().find(|l| l.symbol == "GOLD").unwrap();
+        assert_eq!(gold.path.join(PATH_SEPARATOR), "PMET → GLD → GOLD");
+        assert!((gold.weight - 0.5).abs() < 1e-12);
+        assert!(legs.iter().all(|l| !generator.look_through.contains_key(&l.symbol)));
+
+        let total: f64 = legs.iter().map(|l| l.weight).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_look_through_cycle_stays_on_composite() {
+        let link = |parent: &str, child: &str, weight: f64| Constituent {
+            parent_symbol: parent.into(),
+            constituent_symbol: child.into(),
+            weight,
+            shares_per_unit: 1.0,
+            effective_date: "2024-01-15".into(),
+        };
+        let mut map: HashMap<String, Vec<Constituent>> = HashMap::new();
+        map.insert("A".into(), vec![link("A", "B", 0.5), link("A", "X", 0.5)]);
+        map.insert("B".into(), vec![link("B", "A", 0.4), link("B", "Y", 0.6)]);
+
+        let legs = resolve_look_through(&map, "A");
+        let symbols: Vec<&str> = legs.iter().map(|l| l.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["A", "Y", "X"]);
+        // B's weight back into A is kept on A rather than dropped
+        assert!((legs[0].weight - 0.2).abs() < 1e-12);
+        assert_eq!(legs[0].path, vec!["A"]);
+        assert!((legs[1].weight - 0.3).abs() < 1e-12);
+        assert_eq!(legs[1].path, vec!["A", "B", "Y"]);
+        let total: f64 = legs.iter().map(|l| l.weight).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
     #[test]
     fn test_deterministic_output_with_seed() {
         let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
@@ -693,6 +1000,9 @@ mod tests {
             seed: 42,
             trade_date: "2024-01-15".to_string(),
             explode_constituents: false,
+            fx_rates_output: None,
+            price_history_output: None,
+            price_history_days: 260,
         };
 
         let result = generate_to_file(&args).unwrap();
@@ -721,6 +1031,9 @@ mod tests {
             seed: 42,
             trade_date: "2024-01-15".to_string(),
             explode_constituents: false,
+            fx_rates_output: None,
+            price_history_output: None,
+            price_history_days: 260,
         };
 
         generate_to_file(&args).unwrap();
@@ -737,6 +1050,49 @@ mod tests {
         assert!(constituents_content.contains("GLD,GOLD"));
     }
 
+    #[test]
+    fn test_fx_rates_cover_instrument_currencies() {
+        let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let generator = DataGenerator::new(42, trade_date, 1, false);
+        let rates = generator.fx_rates();
+
+        let mut currencies: Vec<&str> = generator
+            .instruments()
+            .iter()
+            .map(|i| i.currency.as_str())
+            .filter(|c| *c != "USD")
+            .collect();
+        currencies.sort_unstable();
+        currencies.dedup();
+
+        assert!(!currencies.is_empty());
+        assert_eq!(
+            rates.iter().map(|r| r.base_currency.as_str()).collect::<Vec<_>>(),
+            currencies
+        );
+        assert!(rates.iter().all(|r| r.quote_currency == "USD" && r.rate > 0.0));
+        assert!(rates.iter().all(|r| r.rate_date == "2024-01-15"));
+    }
+
+    #[test]
+    fn test_price_history_covers_business_days() {
+        let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let generator = DataGenerator::new(42, trade_date, 1, false);
+        let prices = generator.price_history(10);
+
+        let symbols: std::collections::HashSet<&str> =
+            prices.iter().map(|p| p.symbol.as_str()).collect();
+        assert!(symbols.contains("AAPL"));
+        assert!(symbols.contains("GOLD"));
+        assert_eq!(prices.len(), symbols.len() * 10);
+
+        let dates: std::collections::BTreeSet<&str> =
+            prices.iter().map(|p| p.price_date.as_str()).collect();
+        assert_eq!(dates.iter().next_back(), Some(&"2024-01-15"));
+        assert_eq!(dates.iter().next(), Some(&"2024-01-02"));
+        assert!(prices.iter().all(|p| p.close > 0.0));
+    }
+
     #[test]
     fn test_greeks_in_valid_ranges() {
         let trade_date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();